@@ -2,6 +2,8 @@ use config::{Config, ConfigError, Environment, File};
 use serde::Deserialize;
 
 pub const COOKIE_REALM: &str = "cookie-users";
+// the websub_secret once shipped in config/default.json, known to anyone
+const PUBLIC_WEBSUB_SECRET: &str = "change-me";
 
 #[derive(Debug, Deserialize, Clone)]
 pub struct Settings {
@@ -12,9 +14,42 @@ pub struct Settings {
     pub db_path: String,
     pub app_name: String,
     pub bakery_trigger_cooldown: i64,
+    // s, minimum delay between a channel's last refresh and a manual one
+    pub manual_refresh_cooldown: i64,
     pub default_item_per_feed: i64,
+    // items per page of the user feed, unless the client asks for another size
+    pub feed_page_size: i64,
+    pub feed_max_page_size: i64,
     pub base_refresh_frequency: i32,
+    // lower bound of the adaptive refresh frequency, in minutes
+    pub base_refresh_frequency_min_help: i32,
+    // upper bound of the adaptive refresh frequency, in minutes
+    pub max_refresh_frequency_min_help: i32,
     pub identity_server_addr: String,
+    // s
+    pub refresh_worker_interval: u64,
+    // s, delay before retrying a channel after its first failure, doubled on each new failure
+    pub failure_backoff_base: i64,
+    // s
+    pub failure_backoff_max: i64,
+    // consecutive failures after which a channel is suspended
+    pub failure_suspend_threshold: i32,
+    // consecutive observations of a permanent redirect after which a channel is moved
+    pub permanent_redirect_threshold: i32,
+    // url panya is reachable at by WebSub hubs, WebSub is disabled when empty
+    pub public_url: String,
+    // subscription secrets are derived from it
+    pub websub_secret: String,
+    // s, lease asked to the hubs
+    pub websub_lease_seconds: i64,
+    // safety net refresh frequency of channels pushed by a hub, in minutes
+    pub websub_refresh_frequency_min_help: i32,
+    // outgoing requests running at once, across every host
+    pub fetch_max_concurrency: usize,
+    // outgoing requests running at once to a single host
+    pub fetch_max_per_host: usize,
+    // ms, minimum delay between the start of two requests to the same host
+    pub fetch_host_delay: u64,
 }
 
 // #[derive(Debug, Deserialize)]
@@ -24,6 +59,37 @@ pub struct Settings {
 // }
 
 impl Settings {
+    /// refresh_frequency_bounds returns the adaptive refresh frequency bounds in ms
+    pub fn refresh_frequency_bounds(&self) -> (i32, i32) {
+        (
+            self.base_refresh_frequency_min_help.saturating_mul(60_000),
+            self.max_refresh_frequency_min_help.saturating_mul(60_000),
+        )
+    }
+
+    /// websub_disabled_reason tells why WebSub is disabled, if it is: hubs need a public_url
+    /// to call back, and the signatures of their notifications a secret no one else knows.
+    pub fn websub_disabled_reason(&self) -> Option<&'static str> {
+        if self.public_url.is_empty() {
+            return Some("no public_url is set");
+        }
+        match self.websub_secret.trim() {
+            "" => Some("no websub_secret is set"),
+            PUBLIC_WEBSUB_SECRET => Some("websub_secret is the public default one"),
+            _ => None,
+        }
+    }
+
+    pub fn websub_enabled(&self) -> bool {
+        self.websub_disabled_reason().is_none()
+    }
+
+    /// websub_refresh_frequency returns the refresh frequency of channels pushed by a hub, in ms
+    pub fn websub_refresh_frequency(&self) -> i32 {
+        self.websub_refresh_frequency_min_help
+            .saturating_mul(60_000)
+    }
+
     pub fn new() -> Result<Self, ConfigError> {
         let mut s = Config::new();
 
@@ -43,4 +109,37 @@ impl Settings {
         // Now that we're done, let's access our configuration
         s.try_into()
     }
+
+    /// defaults returns the settings of config/default.json alone, whatever the environment
+    #[cfg(test)]
+    pub fn defaults() -> Self {
+        let mut s = Config::new();
+        s.merge(File::from_str(
+            include_str!("../config/default.json"),
+            config::FileFormat::Json,
+        ))
+        .unwrap();
+        s.try_into().unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_websub_needs_a_private_secret() {
+        let mut settings = Settings::defaults();
+        assert!(!settings.websub_enabled());
+
+        settings.public_url = "https://panya.com".to_string();
+        assert_eq!(
+            settings.websub_disabled_reason(),
+            Some("no websub_secret is set")
+        );
+        settings.websub_secret = "change-me".to_string();
+        assert!(!settings.websub_enabled());
+        settings.websub_secret = "4c0a1e0f2b".to_string();
+        assert!(settings.websub_enabled());
+    }
 }
@@ -0,0 +1,123 @@
+use atom_syndication::{Entry, Feed};
+
+use crate::entities::{channel::Channel, potential_articles::PotentialArticle};
+use crate::error::Error;
+
+/// atom_to_articles parses a raw Atom document and maps its entries
+/// into articles belonging to `channel`.
+pub fn atom_to_articles(
+    raw_data: &[u8],
+    channel: &Channel,
+) -> Result<Vec<PotentialArticle>, Error> {
+    let feed = Feed::read_from(raw_data)?;
+    Ok(feed
+        .entries()
+        .iter()
+        .filter_map(|entry| entry_to_article(entry, channel))
+        .collect())
+}
+
+/// entry_link returns the entry's alternate link, falling back on
+/// its first link, then on its id when the id is an URL.
+fn entry_link(entry: &Entry) -> Option<String> {
+    entry
+        .links()
+        .iter()
+        .find(|link| link.rel() == "alternate")
+        .or(entry.links().first())
+        .map(|link| link.href().to_string())
+        .or_else(|| {
+            entry
+                .id()
+                .starts_with("http")
+                .then(|| entry.id().to_string())
+        })
+}
+
+fn entry_to_article(entry: &Entry, channel: &Channel) -> Option<PotentialArticle> {
+    let link = entry_link(entry)?;
+    let categories: Vec<String> = entry
+        .categories()
+        .iter()
+        .map(|category| category.term().to_string())
+        .collect();
+    let title = entry.title().as_str();
+
+    Some(
+        PotentialArticle {
+            link,
+            img: String::new(),
+            title: (!title.is_empty()).then(|| title.to_string()),
+            desc: entry
+                .summary()
+                .map(|summary| summary.as_str())
+                .or(entry.content().and_then(|content| content.value()))
+                .unwrap_or_default()
+                .to_string(),
+            create_date: entry
+                .published()
+                .unwrap_or(entry.updated())
+                .timestamp_millis(),
+            channel_name: Some(channel.name.clone()),
+            channel_id: Some(channel.id),
+            categories: (!categories.is_empty()).then_some(categories),
+            key: String::new(),
+            content_html: None,
+            content_text: None,
+            enclosure: None,
+        }
+        .with_key(Some(entry.id())),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entities::channel::SourceType;
+
+    #[test]
+    fn test_i_can_convert_atom_to_articles() {
+        let raw = r#"<?xml version="1.0" encoding="utf-8"?>
+<feed xmlns="http://www.w3.org/2005/Atom">
+  <title>Test</title>
+  <id>urn:uuid:60a76c80-d399-11d9-b93C-0003939e0af6</id>
+  <updated>2003-12-13T18:30:02Z</updated>
+  <entry>
+    <title>First</title>
+    <id>urn:uuid:1225c695-cfb8-4ebb-aaaa-80da344efa6a</id>
+    <link rel="edit" href="https://example.com/edit/first"/>
+    <link href="https://example.com/first"/>
+    <updated>2003-12-13T18:30:02Z</updated>
+    <summary>First entry</summary>
+    <category term="news"/>
+  </entry>
+  <entry>
+    <title>Second</title>
+    <id>https://example.com/second</id>
+    <updated>2003-06-10T04:00:00Z</updated>
+  </entry>
+  <entry>
+    <title>No link</title>
+    <id>urn:uuid:1225c695-cfb8-4ebb-aaaa-80da344efa6b</id>
+    <updated>2003-06-10T04:00:00Z</updated>
+  </entry>
+</feed>"#;
+        let mut channel = Channel::new(
+            "example.com",
+            "https://example.com/atom",
+            SourceType::AtomFeed,
+            1000,
+        );
+        channel.id = 3;
+        let articles = atom_to_articles(raw.as_bytes(), &channel).unwrap();
+
+        assert_eq!(articles.len(), 2);
+        assert_eq!(articles[0].link, "https://example.com/first");
+        assert_eq!(articles[0].title, Some("First".to_string()));
+        assert_eq!(articles[0].desc, "First entry");
+        assert_eq!(articles[0].create_date, 1071340202000);
+        assert_eq!(articles[0].categories, Some(vec!["news".to_string()]));
+        assert_eq!(articles[1].link, "https://example.com/second");
+        assert_eq!(articles[1].create_date, 1055217600000);
+    }
+}
@@ -0,0 +1,275 @@
+use chrono::Utc;
+use serde_json::Value;
+use url::Url;
+
+use crate::entities::{
+    channel::{Channel, JsonApiMapping},
+    potential_articles::PotentialArticle,
+};
+use crate::error::Error;
+use crate::services::link_op::resolve_link;
+use crate::utils::parse_date_with_format;
+
+// timestamps above this are taken as milliseconds, the ones below as seconds
+const MS_TIMESTAMP_THRESHOLD: i64 = 100_000_000_000;
+
+/// to_pointer turns a mapping's location into a JSON pointer.
+/// Dotted paths have their keys escaped, "data.a/b" becoming "/data/a~1b".
+fn to_pointer(name: &str, location: &str) -> Result<String, Error> {
+    let location = location.trim();
+    if location.is_empty() || location.starts_with('/') {
+        return Ok(location.to_string());
+    }
+    if location.split('.').any(str::is_empty) {
+        return Err(Error(format!(
+            "invalid {} mapping \"{}\": a path cannot have empty keys",
+            name, location
+        )));
+    }
+    Ok(location
+        .split('.')
+        .map(|key| format!("/{}", key.replace('~', "~0").replace('/', "~1")))
+        .collect())
+}
+
+fn to_optional_pointer(name: &str, location: &Option<String>) -> Result<Option<String>, Error> {
+    location
+        .as_deref()
+        .filter(|location| !location.trim().is_empty())
+        .map(|location| to_pointer(name, location))
+        .transpose()
+}
+
+/// JsonApiPointers are the locations of a channel's JSON API mapping, as JSON pointers
+struct JsonApiPointers {
+    items: String,
+    link: String,
+    title: Option<String>,
+    description: Option<String>,
+    image: Option<String>,
+    date: Option<String>,
+    categories: Option<String>,
+}
+
+impl JsonApiPointers {
+    fn parse(mapping: &JsonApiMapping) -> Result<Self, Error> {
+        let link = to_pointer("link", &mapping.link)?;
+        if link.is_empty() {
+            return Err(Error::str("invalid link mapping: it cannot be empty"));
+        }
+        Ok(JsonApiPointers {
+            items: to_pointer("items", &mapping.items)?,
+            link,
+            title: to_optional_pointer("title", &mapping.title)?,
+            description: to_optional_pointer("description", &mapping.description)?,
+            image: to_optional_pointer("image", &mapping.image)?,
+            date: to_optional_pointer("date", &mapping.date)?,
+            categories: to_optional_pointer("categories", &mapping.categories)?,
+        })
+    }
+}
+
+/// validate_mapping checks that every location of the mapping can be read
+pub fn validate_mapping(mapping: &JsonApiMapping) -> Result<(), Error> {
+    JsonApiPointers::parse(mapping).map(|_| ())
+}
+
+/// string_at returns the string, or the number, found at `pointer` in `item`
+fn string_at(item: &Value, pointer: Option<&str>) -> Option<String> {
+    match item.pointer(pointer?)? {
+        Value::String(value) => Some(value.trim().to_string()),
+        Value::Number(value) => Some(value.to_string()),
+        _ => None,
+    }
+    .filter(|value| !value.is_empty())
+}
+
+fn date_at(item: &Value, pointer: Option<&str>, format: Option<&str>) -> Option<i64> {
+    match item.pointer(pointer?)? {
+        Value::String(date) => parse_date_with_format(date, format),
+        Value::Number(timestamp) => {
+            timestamp
+                .as_i64()
+                .map(|timestamp| match timestamp < MS_TIMESTAMP_THRESHOLD {
+                    true => timestamp * 1000,
+                    false => timestamp,
+                })
+        }
+        _ => None,
+    }
+}
+
+fn categories_at(item: &Value, pointer: Option<&str>) -> Option<Vec<String>> {
+    let categories: Vec<String> = match item.pointer(pointer?)? {
+        Value::Array(values) => values
+            .iter()
+            .filter_map(Value::as_str)
+            .map(str::to_string)
+            .collect(),
+        Value::String(value) => vec![value.clone()],
+        _ => vec![],
+    };
+    (!categories.is_empty()).then_some(categories)
+}
+
+fn item_to_article(
+    item: &Value,
+    pointers: &JsonApiPointers,
+    mapping: &JsonApiMapping,
+    base: &Url,
+    channel: &Channel,
+) -> Option<PotentialArticle> {
+    let link = string_at(item, Some(&pointers.link)).and_then(|link| resolve_link(base, &link))?;
+    Some(
+        PotentialArticle {
+            link,
+            img: string_at(item, pointers.image.as_deref())
+                .and_then(|image| resolve_link(base, &image))
+                .unwrap_or_default(),
+            title: string_at(item, pointers.title.as_deref()),
+            desc: string_at(item, pointers.description.as_deref()).unwrap_or_default(),
+            create_date: date_at(
+                item,
+                pointers.date.as_deref(),
+                mapping.date_format.as_deref(),
+            )
+            .unwrap_or_else(|| Utc::now().timestamp_millis()),
+            channel_name: Some(channel.name.clone()),
+            channel_id: Some(channel.id),
+            categories: categories_at(item, pointers.categories.as_deref()),
+            key: String::new(),
+            content_html: None,
+            content_text: None,
+            enclosure: None,
+        }
+        .with_key(None),
+    )
+}
+
+/// json_api_to_articles reads the items of a JSON API's response with the channel's mapping.
+/// Items without a link are skipped, but none having one is an error.
+pub fn json_api_to_articles(
+    raw_data: &[u8],
+    channel: &Channel,
+) -> Result<Vec<PotentialArticle>, Error> {
+    let mapping = channel
+        .json_api
+        .as_ref()
+        .ok_or_else(|| Error::str("the channel has no JSON API mapping"))?;
+    let pointers = JsonApiPointers::parse(mapping)?;
+    let base = Url::parse(&channel.url).map_err(|err| Error(err.to_string()))?;
+    let document: Value = serde_json::from_slice(raw_data)?;
+    let items = document
+        .pointer(&pointers.items)
+        .and_then(Value::as_array)
+        .ok_or_else(|| {
+            Error(format!(
+                "items mapping \"{}\" does not point to an array",
+                mapping.items
+            ))
+        })?;
+
+    let articles: Vec<PotentialArticle> = items
+        .iter()
+        .filter_map(|item| item_to_article(item, &pointers, mapping, &base, channel))
+        .collect();
+    if articles.is_empty() && !items.is_empty() {
+        return Err(Error(format!(
+            "link mapping \"{}\" does not point to a link in any of the {} items",
+            mapping.link,
+            items.len()
+        )));
+    }
+    Ok(articles)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entities::channel::SourceType;
+
+    const DOCUMENT: &str = r#"{
+        "data": {
+            "releases": [
+                {
+                    "name": "v1.2.0",
+                    "html_url": "/releases/v1.2.0",
+                    "body": "Bug fixes",
+                    "published_at": "2026-10-17T10:00:00Z",
+                    "meta": {"tags": ["stable", "minor"], "cover/image": "https://cdn.com/1.png"}
+                },
+                {"name": "v1.1.0", "html_url": "https://b.com/v1.1.0", "published_at": 1792231200, "meta": {"tags": "stable"}},
+                {"name": "draft"}
+            ]
+        }
+    }"#;
+
+    fn mapping() -> JsonApiMapping {
+        JsonApiMapping {
+            items: "data.releases".to_string(),
+            link: "/html_url".to_string(),
+            title: Some("name".to_string()),
+            description: Some("body".to_string()),
+            image: Some("meta.cover/image".to_string()),
+            date: Some("published_at".to_string()),
+            date_format: None,
+            categories: Some("/meta/tags".to_string()),
+        }
+    }
+
+    fn channel(mapping: JsonApiMapping) -> Channel {
+        Channel {
+            json_api: Some(mapping),
+            ..Channel::new("a.com", "https://a.com/api", SourceType::JSON_API, 1000)
+        }
+    }
+
+    #[test]
+    fn test_i_can_map_json_api_to_articles() {
+        let articles = json_api_to_articles(DOCUMENT.as_bytes(), &channel(mapping())).unwrap();
+
+        assert_eq!(articles.len(), 2);
+        assert_eq!(articles[0].link, "https://a.com/releases/v1.2.0");
+        assert_eq!(articles[0].title, Some("v1.2.0".to_string()));
+        assert_eq!(articles[0].desc, "Bug fixes");
+        assert_eq!(articles[0].img, "https://cdn.com/1.png");
+        assert_eq!(articles[0].create_date, 1792231200000);
+        assert_eq!(
+            articles[0].categories,
+            Some(vec!["stable".to_string(), "minor".to_string()])
+        );
+        assert_eq!(articles[1].create_date, 1792231200000);
+        assert_eq!(articles[1].categories, Some(vec!["stable".to_string()]));
+        assert_eq!(articles[1].desc, "");
+    }
+
+    #[test]
+    fn test_i_cannot_map_json_api_with_a_failing_mapping() {
+        let err = validate_mapping(&JsonApiMapping {
+            title: Some("data..name".to_string()),
+            ..mapping()
+        })
+        .unwrap_err();
+        assert!(err.0.contains("title mapping"));
+
+        let err = json_api_to_articles(
+            DOCUMENT.as_bytes(),
+            &channel(JsonApiMapping {
+                items: "/data".to_string(),
+                ..mapping()
+            }),
+        )
+        .unwrap_err();
+        assert!(err.0.contains("items mapping \"/data\""));
+
+        let err = json_api_to_articles(
+            DOCUMENT.as_bytes(),
+            &channel(JsonApiMapping {
+                link: "url".to_string(),
+                ..mapping()
+            }),
+        )
+        .unwrap_err();
+        assert!(err.0.contains("link mapping \"url\""));
+    }
+}
@@ -0,0 +1,139 @@
+use chrono::Utc;
+use serde::Deserialize;
+
+use crate::entities::{channel::Channel, potential_articles::PotentialArticle};
+use crate::error::Error;
+use crate::utils::parse_feed_date;
+
+/// JSON Feed 1.1 document, see https://www.jsonfeed.org/version/1.1/
+#[derive(Debug, Deserialize)]
+pub struct JsonFeed {
+    #[serde(default)]
+    pub items: Vec<JsonFeedItem>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct JsonFeedItem {
+    pub id: Option<String>,
+    pub url: Option<String>,
+    pub external_url: Option<String>,
+    pub title: Option<String>,
+    pub content_html: Option<String>,
+    pub content_text: Option<String>,
+    pub summary: Option<String>,
+    pub image: Option<String>,
+    pub banner_image: Option<String>,
+    pub date_published: Option<String>,
+    pub date_modified: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+impl JsonFeedItem {
+    /// link returns the item's url, falling back on its external url,
+    /// then on its id when the id is an URL.
+    fn link(&self) -> Option<String> {
+        self.url
+            .clone()
+            .or(self.external_url.clone())
+            .or(self.id.clone().filter(|id| id.starts_with("http")))
+    }
+}
+
+/// json_feed_to_articles parses a raw JSON Feed document and maps its items
+/// into articles belonging to `channel`.
+pub fn json_feed_to_articles(
+    raw_data: &[u8],
+    channel: &Channel,
+) -> Result<Vec<PotentialArticle>, Error> {
+    let feed = serde_json::from_slice::<JsonFeed>(raw_data)?;
+    Ok(feed
+        .items
+        .into_iter()
+        .filter_map(|item| item_to_article(item, channel))
+        .collect())
+}
+
+fn item_to_article(item: JsonFeedItem, channel: &Channel) -> Option<PotentialArticle> {
+    let link = item.link()?;
+    let id = item.id.clone();
+
+    Some(
+        PotentialArticle {
+            link,
+            img: item.image.or(item.banner_image).unwrap_or_default(),
+            title: item.title,
+            desc: item
+                .content_html
+                .or(item.summary)
+                .or(item.content_text)
+                .unwrap_or_default(),
+            create_date: item
+                .date_published
+                .or(item.date_modified)
+                .as_deref()
+                .and_then(parse_feed_date)
+                .unwrap_or_else(|| Utc::now().timestamp_millis()),
+            channel_name: Some(channel.name.clone()),
+            channel_id: Some(channel.id),
+            categories: (!item.tags.is_empty()).then_some(item.tags),
+            key: String::new(),
+            content_html: None,
+            content_text: None,
+            enclosure: None,
+        }
+        .with_key(id.as_deref()),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entities::channel::SourceType;
+
+    #[test]
+    fn test_i_can_convert_json_feed_to_articles() {
+        let raw = r#"{
+            "version": "https://jsonfeed.org/version/1.1",
+            "title": "Test",
+            "items": [
+                {
+                    "id": "1",
+                    "url": "https://example.com/first",
+                    "title": "First",
+                    "content_html": "<p>First item</p>",
+                    "summary": "First",
+                    "image": "https://example.com/first.png",
+                    "date_published": "2003-06-10T04:00:00Z",
+                    "tags": ["news"]
+                },
+                {
+                    "id": "https://example.com/second",
+                    "content_text": "Second item"
+                },
+                {
+                    "id": "3",
+                    "content_text": "No link at all"
+                }
+            ]
+        }"#;
+        let mut channel = Channel::new(
+            "example.com",
+            "https://example.com/feed.json",
+            SourceType::JsonFeed,
+            1000,
+        );
+        channel.id = 3;
+        let articles = json_feed_to_articles(raw.as_bytes(), &channel).unwrap();
+
+        assert_eq!(articles.len(), 2);
+        assert_eq!(articles[0].link, "https://example.com/first");
+        assert_eq!(articles[0].img, "https://example.com/first.png");
+        assert_eq!(articles[0].desc, "<p>First item</p>");
+        assert_eq!(articles[0].create_date, 1055217600000);
+        assert_eq!(articles[0].categories, Some(vec!["news".to_string()]));
+        assert_eq!(articles[1].link, "https://example.com/second");
+        assert_eq!(articles[1].desc, "Second item");
+        assert_eq!(articles[1].categories, None);
+    }
+}
@@ -1 +1,6 @@
+pub mod atom_feed;
+pub mod json_api;
+pub mod json_feed;
+pub mod rss_feed;
+pub mod scraper;
 pub mod string;
@@ -0,0 +1,266 @@
+use chrono::Utc;
+use rss::{extension::Extension, Item};
+
+use crate::entities::{
+    channel::Channel,
+    potential_articles::{Enclosure, PotentialArticle},
+};
+use crate::error::Error;
+use crate::services::images::{images_in_html, pick_image, ImageCandidate};
+use crate::utils::parse_feed_date;
+
+const MEDIA_RSS_NAMESPACE: &str = "http://search.yahoo.com/mrss/";
+
+/// rss_to_articles parses a raw RSS document and maps its items
+/// into articles belonging to `channel`.
+pub fn rss_to_articles(raw_data: &[u8], channel: &Channel) -> Result<Vec<PotentialArticle>, Error> {
+    let rss_channel = rss::Channel::read_from(raw_data)?;
+    // the prefix the document binds Media RSS to, usually "media"
+    let media_prefix = rss_channel
+        .namespaces()
+        .iter()
+        .find(|(_, url)| url.trim_end_matches('/') == MEDIA_RSS_NAMESPACE.trim_end_matches('/'))
+        .map(|(prefix, _)| prefix.as_str())
+        .unwrap_or("media");
+    Ok(rss_channel
+        .items()
+        .iter()
+        .filter_map(|item| item_to_article(item, channel, media_prefix))
+        .collect())
+}
+
+fn media_candidate(extension: &Extension) -> Option<ImageCandidate> {
+    let attrs = extension.attrs();
+    let url = attrs.get("url")?;
+    // without a type, a medium other than "image" stands for another kind of media
+    let medium = attrs
+        .get("medium")
+        .filter(|medium| medium.as_str() != "image")
+        .map(|medium| format!("{}/*", medium));
+    let candidate = ImageCandidate::new(
+        url,
+        attrs.get("type").or(medium.as_ref()).map(String::as_str),
+        attrs.get("width").map(String::as_str),
+    );
+    Some(match extension.name() {
+        "thumbnail" => candidate.image(),
+        _ => candidate,
+    })
+}
+
+/// image_candidates lists the images of an item: its enclosure, its Media RSS
+/// contents and thumbnails, including the ones of a media:group, and the `<img>` of its description.
+fn image_candidates(item: &Item, media_prefix: &str) -> Vec<ImageCandidate> {
+    let mut candidates = vec![];
+    if let Some(enclosure) = item.enclosure() {
+        candidates.push(ImageCandidate::new(
+            enclosure.url(),
+            Some(enclosure.mime_type()),
+            None,
+        ));
+    }
+    if let Some(media) = item.extensions().get(media_prefix) {
+        let groups = media.get("group").into_iter().flatten();
+        for elements in [media.get("content"), media.get("thumbnail")]
+            .into_iter()
+            .flatten()
+            .chain(groups.flat_map(|group| group.children().values()))
+        {
+            candidates.extend(elements.iter().filter_map(media_candidate));
+        }
+    }
+    for html in [item.description(), item.content()].into_iter().flatten() {
+        candidates.extend(images_in_html(html));
+    }
+    candidates
+}
+
+/// item_enclosure maps the item's enclosure, along with its itunes:duration if any
+fn item_enclosure(item: &Item) -> Option<Enclosure> {
+    let enclosure = item
+        .enclosure()
+        .filter(|enclosure| !enclosure.url().is_empty())?;
+    Some(Enclosure {
+        url: enclosure.url().trim().to_string(),
+        mime_type: enclosure.mime_type().trim().to_string(),
+        length: enclosure.length().trim().parse().unwrap_or_default(),
+        duration: item
+            .itunes_ext()
+            .and_then(|itunes| itunes.duration())
+            .map(|duration| duration.trim().to_string())
+            .filter(|duration| !duration.is_empty()),
+    })
+}
+
+/// item_to_article maps a single RSS item. Items without any link
+/// (neither `<link>` nor a permalink `<guid>`) are dropped.
+fn item_to_article(item: &Item, channel: &Channel, media_prefix: &str) -> Option<PotentialArticle> {
+    let link = item.link().map(str::to_string).or_else(|| {
+        item.guid()
+            .filter(|guid| guid.is_permalink())
+            .map(|guid| guid.value().to_string())
+    })?;
+    let categories: Vec<String> = item
+        .categories()
+        .iter()
+        .map(|category| category.name().to_string())
+        .collect();
+
+    Some(
+        PotentialArticle {
+            img: pick_image(&image_candidates(item, media_prefix), &channel.url)
+                .unwrap_or_default(),
+            link,
+            title: item.title().map(str::to_string),
+            desc: item
+                .description()
+                .or(item.content())
+                .unwrap_or_default()
+                .to_string(),
+            create_date: item
+                .pub_date()
+                .and_then(parse_feed_date)
+                .unwrap_or_else(|| Utc::now().timestamp_millis()),
+            channel_name: Some(channel.name.clone()),
+            channel_id: Some(channel.id),
+            categories: (!categories.is_empty()).then_some(categories),
+            key: String::new(),
+            content_html: None,
+            content_text: None,
+            enclosure: item_enclosure(item),
+        }
+        .with_key(item.guid().map(|guid| guid.value())),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entities::channel::SourceType;
+
+    #[test]
+    fn test_i_can_convert_rss_to_articles() {
+        let raw = r#"<?xml version="1.0"?>
+<rss version="2.0">
+  <channel>
+    <title>Test</title>
+    <link>https://example.com</link>
+    <description>Test feed</description>
+    <item>
+      <title>First</title>
+      <link>https://example.com/first</link>
+      <description>First article</description>
+      <pubDate>Tue, 10 Jun 2003 04:00:00 GMT</pubDate>
+      <category>news</category>
+    </item>
+    <item>
+      <guid isPermaLink="true">https://example.com/second</guid>
+      <description>Second article</description>
+    </item>
+    <item>
+      <description>No link at all</description>
+    </item>
+  </channel>
+</rss>"#;
+        let mut channel = Channel::new(
+            "example.com",
+            "https://example.com/feed",
+            SourceType::RSSFeed,
+            1000,
+        );
+        channel.id = 3;
+        let articles = rss_to_articles(raw.as_bytes(), &channel).unwrap();
+
+        assert_eq!(articles.len(), 2);
+        assert_eq!(articles[0].link, "https://example.com/first");
+        assert_eq!(articles[0].title, Some("First".to_string()));
+        assert_eq!(articles[0].create_date, 1055217600000);
+        assert_eq!(articles[0].categories, Some(vec!["news".to_string()]));
+        assert_eq!(articles[0].channel_id, Some(3));
+        assert_eq!(articles[1].link, "https://example.com/second");
+        assert_eq!(articles[1].categories, None);
+        assert_eq!(articles[0].img, "");
+    }
+
+    #[test]
+    fn test_i_can_pick_item_images() {
+        let raw = r#"<?xml version="1.0"?>
+<rss version="2.0" xmlns:m="http://search.yahoo.com/mrss/">
+  <channel>
+    <title>Test</title>
+    <link>https://example.com</link>
+    <description>Test feed</description>
+    <item>
+      <link>https://example.com/enclosure</link>
+      <enclosure url="/images/cover.jpg" length="1000" type="image/jpeg"/>
+      <m:thumbnail url="https://example.com/thumb.jpg" width="150"/>
+    </item>
+    <item>
+      <link>https://example.com/media</link>
+      <enclosure url="https://example.com/episode.mp3" length="1000" type="audio/mpeg"/>
+      <m:group>
+        <m:content url="https://example.com/small.jpg" medium="image" width="300"/>
+        <m:content url="https://example.com/large.jpg" type="image/jpeg" width="1200"/>
+        <m:content url="https://example.com/video.mp4" medium="video" width="1920"/>
+      </m:group>
+    </item>
+    <item>
+      <link>https://example.com/description</link>
+      <description><![CDATA[<p>Hello <img src="pics/hello.png"></p>]]></description>
+    </item>
+  </channel>
+</rss>"#;
+        let channel = Channel::new(
+            "example.com",
+            "https://example.com/blog/feed.xml",
+            SourceType::RSSFeed,
+            1000,
+        );
+        let articles = rss_to_articles(raw.as_bytes(), &channel).unwrap();
+
+        assert_eq!(articles[0].img, "https://example.com/images/cover.jpg");
+        assert_eq!(articles[1].img, "https://example.com/large.jpg");
+        assert_eq!(articles[2].img, "https://example.com/blog/pics/hello.png");
+    }
+
+    #[test]
+    fn test_i_can_read_enclosures() {
+        let raw = r#"<?xml version="1.0"?>
+<rss version="2.0" xmlns:itunes="http://www.itunes.com/dtds/podcast-1.0.dtd">
+  <channel>
+    <title>Podcast</title>
+    <link>https://example.com</link>
+    <description>Test podcast</description>
+    <item>
+      <title>Episode 1</title>
+      <guid isPermaLink="false">episode-1</guid>
+      <link>https://example.com/1</link>
+      <enclosure url="https://example.com/1.mp3" length="24986239" type="audio/mpeg"/>
+      <itunes:duration>01:02:03</itunes:duration>
+    </item>
+    <item>
+      <link>https://example.com/2</link>
+    </item>
+  </channel>
+</rss>"#;
+        let channel = Channel::new(
+            "example.com",
+            "https://example.com/podcast.xml",
+            SourceType::RSSFeed,
+            1000,
+        );
+        let articles = rss_to_articles(raw.as_bytes(), &channel).unwrap();
+
+        assert_eq!(
+            articles[0].enclosure,
+            Some(Enclosure {
+                url: "https://example.com/1.mp3".to_string(),
+                mime_type: "audio/mpeg".to_string(),
+                length: 24986239,
+                duration: Some("01:02:03".to_string()),
+            })
+        );
+        assert_eq!(articles[0].img, "");
+        assert_eq!(articles[1].enclosure, None);
+    }
+}
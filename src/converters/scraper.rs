@@ -0,0 +1,234 @@
+use chrono::Utc;
+use scraper::{ElementRef, Html, Selector};
+use url::Url;
+
+use crate::entities::{
+    channel::{Channel, ScraperRules},
+    potential_articles::PotentialArticle,
+};
+use crate::error::Error;
+use crate::services::link_op::resolve_link;
+use crate::utils::parse_date_with_format;
+
+/// ScraperSelectors are the parsed selectors of a channel's scraper rules
+struct ScraperSelectors {
+    item: Selector,
+    link: Option<Selector>,
+    title: Option<Selector>,
+    description: Option<Selector>,
+    image: Option<Selector>,
+    date: Option<Selector>,
+    any_link: Selector,
+}
+
+fn parse_selector(name: &str, selector: &str) -> Result<Selector, Error> {
+    Selector::parse(selector).map_err(|err| {
+        Error(format!(
+            "invalid {} selector \"{}\": {:?}",
+            name, selector, err
+        ))
+    })
+}
+
+fn parse_optional_selector(
+    name: &str,
+    selector: &Option<String>,
+) -> Result<Option<Selector>, Error> {
+    selector
+        .as_deref()
+        .filter(|selector| !selector.trim().is_empty())
+        .map(|selector| parse_selector(name, selector))
+        .transpose()
+}
+
+impl ScraperSelectors {
+    fn parse(rules: &ScraperRules) -> Result<Self, Error> {
+        Ok(ScraperSelectors {
+            item: parse_selector("item", &rules.item)?,
+            link: parse_optional_selector("link", &rules.link)?,
+            title: parse_optional_selector("title", &rules.title)?,
+            description: parse_optional_selector("description", &rules.description)?,
+            image: parse_optional_selector("image", &rules.image)?,
+            date: parse_optional_selector("date", &rules.date)?,
+            any_link: parse_selector("link", "a[href]")?,
+        })
+    }
+}
+
+/// validate_rules checks that every selector of the rules can be parsed
+pub fn validate_rules(rules: &ScraperRules) -> Result<(), Error> {
+    ScraperSelectors::parse(rules).map(|_| ())
+}
+
+fn text(element: &ElementRef) -> String {
+    element
+        .text()
+        .collect::<Vec<&str>>()
+        .join(" ")
+        .split_whitespace()
+        .collect::<Vec<&str>>()
+        .join(" ")
+}
+
+fn select_first<'a>(item: &ElementRef<'a>, selector: &Option<Selector>) -> Option<ElementRef<'a>> {
+    item.select(selector.as_ref()?).next()
+}
+
+fn item_to_article(
+    item: ElementRef,
+    selectors: &ScraperSelectors,
+    rules: &ScraperRules,
+    base: &Url,
+    channel: &Channel,
+) -> Option<PotentialArticle> {
+    let link_element = match &selectors.link {
+        Some(_) => select_first(&item, &selectors.link)?,
+        None => item,
+    };
+    let link = link_element
+        .value()
+        .attr("href")
+        .or_else(|| {
+            link_element
+                .select(&selectors.any_link)
+                .next()
+                .and_then(|a| a.value().attr("href"))
+        })
+        .and_then(|href| resolve_link(base, href))?;
+    let title = match &selectors.title {
+        Some(_) => select_first(&item, &selectors.title).map(|title| text(&title)),
+        None => Some(text(&link_element)),
+    }
+    .filter(|title| !title.is_empty());
+    let img = select_first(&item, &selectors.image)
+        .and_then(|image| {
+            let image = image.value();
+            image
+                .attr("src")
+                .or(image.attr("data-src"))
+                .or(image.attr("content"))
+                .or(image.attr("href"))
+                .and_then(|src| resolve_link(base, src))
+        })
+        .unwrap_or_default();
+    let create_date = select_first(&item, &selectors.date)
+        .and_then(|date| {
+            let value = date
+                .value()
+                .attr("datetime")
+                .or(date.value().attr("content"))
+                .map(str::to_string)
+                .unwrap_or_else(|| text(&date));
+            parse_date_with_format(&value, rules.date_format.as_deref())
+        })
+        .unwrap_or_else(|| Utc::now().timestamp_millis());
+
+    Some(
+        PotentialArticle {
+            link,
+            img,
+            title,
+            desc: select_first(&item, &selectors.description)
+                .map(|description| description.inner_html().trim().to_string())
+                .unwrap_or_default(),
+            create_date,
+            channel_name: Some(channel.name.clone()),
+            channel_id: Some(channel.id),
+            categories: None,
+            key: String::new(),
+            content_html: None,
+            content_text: None,
+            enclosure: None,
+        }
+        .with_key(None),
+    )
+}
+
+/// scraper_to_articles reads the items of a web page with the channel's scraper rules.
+/// Items without a link are skipped.
+pub fn scraper_to_articles(
+    raw_data: &[u8],
+    channel: &Channel,
+) -> Result<Vec<PotentialArticle>, Error> {
+    let rules = channel
+        .scraper
+        .as_ref()
+        .ok_or_else(|| Error::str("the channel has no scraper rules"))?;
+    let selectors = ScraperSelectors::parse(rules)?;
+    let base = Url::parse(&channel.url).map_err(|err| Error(err.to_string()))?;
+    let document = Html::parse_document(&String::from_utf8_lossy(raw_data));
+
+    Ok(document
+        .select(&selectors.item)
+        .filter_map(|item| item_to_article(item, &selectors, rules, &base, channel))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entities::channel::SourceType;
+
+    const PAGE: &str = r#"<html><body>
+<ul class="news">
+  <li class="entry">
+    <a class="title" href="/news/1">  First
+      news </a>
+    <img src="/img/1.png">
+    <p class="summary">The <b>first</b> one.</p>
+    <span class="date">18/10/2026 08:30</span>
+  </li>
+  <li class="entry">
+    <a class="title" href="https://other.com/2">Second news</a>
+    <time datetime="2026-10-17T10:00:00Z">Yesterday</time>
+  </li>
+  <li class="entry"><span class="title">No link</span></li>
+</ul>
+</body></html>"#;
+
+    fn rules() -> ScraperRules {
+        ScraperRules {
+            item: "li.entry".to_string(),
+            link: Some("a.title".to_string()),
+            title: None,
+            description: Some("p.summary".to_string()),
+            image: Some("img".to_string()),
+            date: Some(".date, time".to_string()),
+            date_format: Some("%d/%m/%Y %H:%M".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_i_can_scrape_articles() {
+        let channel = Channel {
+            scraper: Some(rules()),
+            ..Channel::new("a.com", "https://a.com/news", SourceType::SCRAPER, 1000)
+        };
+        let articles = scraper_to_articles(PAGE.as_bytes(), &channel).unwrap();
+
+        assert_eq!(articles.len(), 2);
+        assert_eq!(articles[0].link, "https://a.com/news/1");
+        assert_eq!(articles[0].title, Some("First news".to_string()));
+        assert_eq!(articles[0].img, "https://a.com/img/1.png");
+        assert_eq!(articles[0].desc, "The <b>first</b> one.");
+        assert_eq!(articles[0].create_date, 1792312200000);
+        assert_eq!(articles[0].channel_id, Some(channel.id));
+        assert!(!articles[0].key.is_empty());
+        assert_eq!(articles[1].link, "https://other.com/2");
+        assert_eq!(articles[1].img, "");
+        // the datetime attribute is not in the rules' format, but is a RFC 3339 date
+        assert_eq!(articles[1].create_date, 1792231200000);
+    }
+
+    #[test]
+    fn test_i_cannot_scrape_with_invalid_rules() {
+        let rules = ScraperRules {
+            item: "li[".to_string(),
+            ..rules()
+        };
+        assert!(validate_rules(&rules).is_err());
+
+        let channel = Channel::new("a.com", "https://a.com/news", SourceType::SCRAPER, 1000);
+        assert!(scraper_to_articles(PAGE.as_bytes(), &channel).is_err());
+    }
+}
@@ -2,8 +2,15 @@ use crate::entities::potential_articles::PotentialArticle;
 
 pub fn to_articles(raw_data: &str) -> Vec<PotentialArticle>
 {
-    serde_json::from_str::<Vec<PotentialArticle>>(raw_data).unwrap_or_else(|err| {
-        error!("could not deserialize into articles: {}", err);
-        vec![]
-    })
+    serde_json::from_str::<Vec<PotentialArticle>>(raw_data)
+        .map(|articles| {
+            articles
+                .into_iter()
+                .map(|article| article.with_key(None))
+                .collect()
+        })
+        .unwrap_or_else(|err| {
+            error!("could not deserialize into articles: {}", err);
+            vec![]
+        })
 }
\ No newline at end of file
@@ -2,9 +2,16 @@ use super::{
     model::{CollectionModel, CollectionModelConstraint},
     mongo::Handle,
 };
-use crate::{entities::channel::Channel, error::Error};
+use crate::{
+    entities::channel::{Channel, ChannelHealth, Redirect, WebSubSubscription},
+    error::Error,
+};
 use chrono::Utc;
-use mongodb::{bson::doc, results::InsertManyResult, Collection, Database};
+use mongodb::{
+    bson::{doc, to_bson},
+    results::InsertManyResult,
+    Collection, Database,
+};
 use serde::Serialize;
 use std::fmt::Debug;
 
@@ -35,10 +42,13 @@ impl<'a> Channels<'a, Channel> {
         &self.db_name
     }
 
+    /// update_refresh sets `last_refresh` to now, and `last_successful_refresh`
+    /// as well if `successful` is true.
     pub async fn update_refresh(
         &self,
         channel_id: impl Into<Option<i32>>,
         channel_name: impl Into<Option<&str>>,
+        successful: bool,
     ) -> Option<()> {
         let mut doc = None;
         if let Some(id) = channel_id.into() {
@@ -47,16 +57,154 @@ impl<'a> Channels<'a, Channel> {
             doc = Some(doc! {"name": name});
         }
         let uw_doc = doc?;
+        let now = Utc::now().timestamp();
+        let mut set = doc! {"last_refresh": now};
+        if successful {
+            set.insert("last_successful_refresh", now);
+        }
+
+        self.collection()
+            .update_one(uw_doc, doc! {"$set": set}, None)
+            .await
+            .ok()
+            .and(Some(()))
+    }
+
+    /// reserve_refresh sets `last_refresh` to now unless the channel was refreshed
+    /// less than `cooldown` (s) ago, telling if it did. Concurrent callers cannot
+    /// both reserve the same refresh.
+    pub async fn reserve_refresh(&self, channel_id: i32, cooldown: i64) -> Result<bool, Error> {
+        let now = Utc::now().timestamp();
+        let reserved = self
+            .collection()
+            .find_one_and_update(
+                doc! {"id": channel_id, "last_refresh": {"$lte": now - cooldown}},
+                doc! {"$set": {"last_refresh": now}},
+                None,
+            )
+            .await?;
+        Ok(reserved.is_some())
+    }
+
+    /// update_refresh_frequency persists the refresh frequency (ms) computed for a channel
+    pub async fn update_refresh_frequency(
+        &self,
+        channel_id: i32,
+        refresh_frequency: i32,
+    ) -> Option<()> {
+        self.collection()
+            .update_one(
+                doc! {"id": channel_id},
+                doc! {"$set": {"refresh_frequency": refresh_frequency}},
+                None,
+            )
+            .await
+            .ok()
+            .and(Some(()))
+    }
+
+    /// update_validators counts a conditional fetch of a channel: a 304 response
+    /// when `not_modified` is true, otherwise a 200 response whose `ETag` and
+    /// `Last-Modified` headers are stored for the next fetch.
+    pub async fn update_validators(
+        &self,
+        channel_id: i32,
+        etag: Option<&str>,
+        last_modified: Option<&str>,
+        not_modified: bool,
+    ) -> Option<()> {
+        let update = if not_modified {
+            doc! {"$inc": {"not_modified_count": 1}}
+        } else {
+            doc! {
+                "$set": {"etag": etag, "last_modified": last_modified},
+                "$inc": {"modified_count": 1},
+            }
+        };
+
+        self.collection()
+            .update_one(doc! {"id": channel_id}, update, None)
+            .await
+            .ok()
+            .and(Some(()))
+    }
+
+    /// update_redirect persists the permanent redirect observed for a channel,
+    /// or clears it when `redirect` is None.
+    pub async fn update_redirect(
+        &self,
+        channel_id: i32,
+        redirect: Option<&Redirect>,
+    ) -> Option<()> {
+        let redirect = to_bson(&redirect).ok()?;
 
         self.collection()
             .update_one(
-                uw_doc,
+                doc! {"id": channel_id},
+                doc! {"$set": {"redirect": redirect}},
+                None,
+            )
+            .await
+            .ok()
+            .and(Some(()))
+    }
+
+    /// update_websub persists the WebSub subscription of a channel,
+    /// or clears it when `websub` is None.
+    pub async fn update_websub(
+        &self,
+        channel_id: i32,
+        websub: Option<&WebSubSubscription>,
+    ) -> Option<()> {
+        let websub = to_bson(&websub).ok()?;
+
+        self.collection()
+            .update_one(
+                doc! {"id": channel_id},
+                doc! {"$set": {"websub": websub}},
+                None,
+            )
+            .await
+            .ok()
+            .and(Some(()))
+    }
+
+    /// update_location moves a channel to a new url and name, clearing its redirect and
+    /// its conditional fetch validators, which belonged to the previous url.
+    pub async fn update_location(
+        &self,
+        channel_id: i32,
+        url: &str,
+        name: &str,
+    ) -> Result<(), Error> {
+        self.collection()
+            .update_one(
+                doc! {"id": channel_id},
                 doc! {"$set": {
-                    "last_refresh": Utc::now().timestamp(),
+                    "url": url,
+                    "name": name,
+                    "redirect": null,
+                    "etag": null,
+                    "last_modified": null,
                 }},
                 None,
             )
             .await
+            .map(|_| ())
+            .map_err(Error::from)
+    }
+
+    /// update_health persists the health of a channel
+    pub async fn update_health(&self, channel_id: i32, health: &ChannelHealth) -> Option<()> {
+        let health = to_bson(health).ok()?;
+
+        self.collection()
+            .update_one(
+                doc! {"id": channel_id},
+                doc! {"$set": {"health": health}},
+                None,
+            )
+            .await
             .ok()
             .and(Some(()))
     }
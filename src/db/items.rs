@@ -1,9 +1,12 @@
 use super::{
-    model::{CollectionModel, CollectionModelConstraint},
+    model::{CollectionModel, CollectionModelConstraint, UpsertManyResult},
     mongo::Handle,
 };
-use crate::error::Error;
-use mongodb::{bson::doc, results::InsertManyResult, Collection, Database, IndexModel};
+use crate::{
+    entities::{channel::Channel, potential_articles::PotentialArticle},
+    error::Error,
+};
+use mongodb::{bson::doc, options::IndexOptions, Collection, Database, IndexModel};
 use serde::Serialize;
 use std::fmt::Debug;
 
@@ -15,19 +18,32 @@ pub struct Items<'a, T: Serialize> {
 }
 
 impl<'a, T: CollectionModelConstraint<i32>> Items<'a, T> {
-    pub async fn insert_many(
-        &self,
-        data: &[T],
-        index: Option<String>,
-    ) -> Result<InsertManyResult, Error> {
-        let idx = index.unwrap_or_else(|| "create_date".to_string());
-        // Works cause we dont store result, nor do we return it.
-        // An Err() is returned, if that's the case.
+    /// create_indexes is meant to be called once, at startup.
+    /// Items stored before the `key` field existed are left out of the unique index.
+    pub async fn create_indexes(&self) -> Result<(), Error> {
         self.collection()
-            // Oftenly creating new collectionm therefore index
-            .create_index(IndexModel::builder().keys(doc! {idx: -1}).build(), None)
-            .await?;
-        CollectionModel::<i32, T>::insert_many(self, data).await
+            .create_indexes(
+                [
+                    IndexModel::builder().keys(doc! {"create_date": -1}).build(),
+                    // pages of the user feed, see CollectionModel::find_page
+                    IndexModel::builder()
+                        .keys(doc! {"channel_id": 1, "create_date": -1, "_id": -1})
+                        .build(),
+                    IndexModel::builder()
+                        .keys(doc! {"channel_id": 1, "key": 1})
+                        .options(
+                            IndexOptions::builder()
+                                .unique(true)
+                                .partial_filter_expression(doc! {"key": {"$gt": ""}})
+                                .build(),
+                        )
+                        .build(),
+                ],
+                None,
+            )
+            .await
+            .map(|_| ())
+            .map_err(Error::from)
     }
 
     pub fn get_database_name(&self) -> &String {
@@ -48,6 +64,73 @@ impl<'a, T: CollectionModelConstraint<i32>> Items<'a, T> {
     }
 }
 
+impl<'a> Items<'a, PotentialArticle> {
+    /// rename_channel sets the channel name of every item of a channel
+    pub async fn rename_channel(&self, channel_id: i32, channel_name: &str) -> Result<(), Error> {
+        self.collection()
+            .update_many(
+                doc! {"channel_id": channel_id},
+                doc! {"$set": {"channel_name": channel_name}},
+                None,
+            )
+            .await
+            .map(|_| ())
+            .map_err(Error::from)
+    }
+
+    /// move_to_channel moves every item of the channel `from` to the channel `to`.
+    /// Items already found in `to` are kept as they are.
+    pub async fn move_to_channel(
+        &self,
+        from: i32,
+        to: &Channel,
+    ) -> Result<UpsertManyResult, Error> {
+        let items = self
+            .find(doc! {"channel_id": from}, None, None, None)
+            .await
+            .unwrap_or_default()
+            .into_iter()
+            .map(|item| {
+                // items stored before keys existed get one, so they are not merged together
+                let item = match item.key.is_empty() {
+                    true => item.with_key(None),
+                    false => item,
+                };
+                PotentialArticle {
+                    channel_id: Some(to.id),
+                    channel_name: Some(to.name.clone()),
+                    ..item
+                }
+            })
+            .collect::<Vec<PotentialArticle>>();
+        let res = if items.is_empty() {
+            UpsertManyResult::default()
+        } else {
+            self.upsert_many(&items).await?
+        };
+        self.collection()
+            .delete_many(doc! {"channel_id": from}, None)
+            .await?;
+        Ok(res)
+    }
+
+    /// upsert_many stores articles, deduplicated on their channel and key.
+    /// The `create_date` of an article is kept from its first insertion.
+    pub async fn upsert_many(&self, data: &[PotentialArticle]) -> Result<UpsertManyResult, Error> {
+        let documents = data
+            .iter()
+            .map(PotentialArticle::to_db_document)
+            .collect::<Result<Vec<_>, _>>()?;
+        CollectionModel::<i32, PotentialArticle>::upsert_many(
+            self,
+            documents,
+            &["channel_id", "key"],
+            &["create_date"],
+        )
+        .await
+    }
+}
+
 impl<'a, P: PartialEq + Into<mongodb::bson::Bson> + Clone, T: CollectionModelConstraint<P>>
     CollectionModel<P, T> for Items<'a, T>
 {
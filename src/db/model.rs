@@ -1,7 +1,7 @@
 use crate::error::Error;
 use futures::{StreamExt, TryStreamExt};
 use mongodb::{
-    bson::{doc, to_document, Bson, Document},
+    bson::{doc, oid::ObjectId, to_document, Bson, Document},
     options::{FindOneAndUpdateOptions, FindOptions, InsertOneOptions},
     results::{DeleteResult, InsertManyResult, InsertOneResult},
     Collection, Database,
@@ -10,6 +10,7 @@ use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use std::{
     collections::HashMap,
     fmt::{Debug, Display},
+    ops::AddAssign,
     vec,
 };
 use thiserror::Error;
@@ -79,6 +80,119 @@ struct DocsWrapper<T> {
     docs: Vec<T>,
 }
 
+#[derive(Debug, Default, Serialize)]
+pub struct UpsertManyResult {
+    pub inserted: usize,
+    pub updated: usize,
+    pub unchanged: usize,
+}
+
+impl AddAssign for UpsertManyResult {
+    fn add_assign(&mut self, other: Self) {
+        self.inserted += other.inserted;
+        self.updated += other.updated;
+        self.unchanged += other.unchanged;
+    }
+}
+
+// an update command stays well under the 16MiB a command can weigh
+const BULK_UPDATE_MAX_BYTES: usize = 8 * 1024 * 1024;
+const BULK_UPDATE_MAX_STATEMENTS: usize = 1000;
+
+/// bulk_update runs the update `statements` on `collection` in a single update command
+async fn bulk_update(
+    database: &Database,
+    collection: &str,
+    statements: Vec<Document>,
+) -> Result<UpsertManyResult, Error> {
+    let response = database
+        .run_command(
+            doc! {"update": collection, "updates": statements, "ordered": false},
+            None,
+        )
+        .await?;
+    if let Ok(write_errors) = response.get_array("writeErrors") {
+        let message = write_errors
+            .iter()
+            .filter_map(|err| err.as_document()?.get_str("errmsg").ok())
+            .collect::<Vec<&str>>()
+            .join(", ");
+        return Err(Error(format!(
+            "{} updates failed: {}",
+            write_errors.len(),
+            message
+        )));
+    }
+    let matched = response.get_i32("n").unwrap_or_default() as usize;
+    let inserted = response
+        .get_array("upserted")
+        .map(|upserted| upserted.len())
+        .unwrap_or_default();
+    let updated = response.get_i32("nModified").unwrap_or_default() as usize;
+    Ok(UpsertManyResult {
+        inserted,
+        updated,
+        unchanged: matched.saturating_sub(inserted + updated),
+    })
+}
+
+/// PageCursor is a position within documents sorted on an integer field, descending,
+/// their `_id` breaking ties. It is handed out opaque, encoded as hex.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PageCursor {
+    pub value: i64,
+    pub id: ObjectId,
+}
+
+impl PageCursor {
+    pub fn encode(&self) -> String {
+        hex::encode(format!("{}.{}", self.value, self.id.to_hex()))
+    }
+
+    pub fn decode(cursor: &str) -> Result<Self, Error> {
+        let invalid = || Error(format!("invalid cursor \"{}\"", cursor));
+        let decoded = hex::decode(cursor).map_err(|_| invalid())?;
+        let decoded = String::from_utf8(decoded).map_err(|_| invalid())?;
+        let (value, id) = decoded.split_once('.').ok_or_else(invalid)?;
+        Ok(PageCursor {
+            value: value.parse().map_err(|_| invalid())?,
+            id: ObjectId::parse_str(id).map_err(|_| invalid())?,
+        })
+    }
+
+    /// from_document returns the cursor pointing at `document`
+    fn from_document(document: &Document, field: &str) -> Option<Self> {
+        let value = match document.get(field)? {
+            Bson::Int64(value) => *value,
+            Bson::Int32(value) => *value as i64,
+            Bson::Double(value) => *value as i64,
+            _ => return None,
+        };
+        Some(PageCursor {
+            value,
+            id: document.get_object_id("_id").ok()?,
+        })
+    }
+}
+
+/// PageBound tells which side of a cursor a page is read from
+#[derive(Clone, Debug)]
+pub enum PageBound {
+    Before(PageCursor),
+    After(PageCursor),
+}
+
+/// Page is a page of documents, sorted on a field descending.
+/// `first` and `last` point at its first and last documents.
+#[derive(Debug)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub first: Option<PageCursor>,
+    pub last: Option<PageCursor>,
+    // more documents lie beyond the page, in the direction it was read in
+    pub has_more: bool,
+}
+
 #[derive(Debug, Error)]
 pub struct ModelError {
     err: Option<Error>,
@@ -140,6 +254,58 @@ pub trait CollectionModel<P: PartialEq + Into<Bson> + Clone, T: CollectionModelC
             .map_err(Error::from)
     }
 
+    /// upsert_many inserts the documents that match no existing document on the `keys` fields,
+    /// and updates the ones that do. Fields listed in `insert_only` are only written on insertion.
+    async fn upsert_many(
+        &self,
+        data: Vec<Document>,
+        keys: &[&str],
+        insert_only: &[&str],
+    ) -> Result<UpsertManyResult, Error> {
+        let database = self.get_database().ok_or_else(db_not_found_err)?;
+        let collection = self.get_collection_name();
+        let statements = data.into_iter().map(|mut set| {
+            let mut filter = doc! {};
+            for key in keys {
+                filter.insert(*key, set.get(*key).cloned().unwrap_or(Bson::Null));
+            }
+            let mut set_on_insert = doc! {};
+            for field in insert_only {
+                if let Some(value) = set.remove(*field) {
+                    set_on_insert.insert(*field, value);
+                }
+            }
+            let mut update = doc! {"$set": set};
+            if !set_on_insert.is_empty() {
+                update.insert("$setOnInsert", set_on_insert);
+            }
+            doc! {"q": filter, "u": update, "upsert": true}
+        });
+
+        // statements are sent in as few update commands as their size allows
+        let mut result = UpsertManyResult::default();
+        let mut batch = vec![];
+        let mut batch_bytes = 0;
+        for statement in statements {
+            let bytes = mongodb::bson::to_vec(&statement)
+                .map(|bytes| bytes.len())
+                .unwrap_or_default();
+            if !batch.is_empty()
+                && (batch.len() >= BULK_UPDATE_MAX_STATEMENTS
+                    || batch_bytes + bytes > BULK_UPDATE_MAX_BYTES)
+            {
+                result += bulk_update(database, &collection, std::mem::take(&mut batch)).await?;
+                batch_bytes = 0;
+            }
+            batch_bytes += bytes;
+            batch.push(statement);
+        }
+        if !batch.is_empty() {
+            result += bulk_update(database, &collection, batch).await?;
+        }
+        Ok(result)
+    }
+
     async fn insert_one(
         &self,
         data: &T,
@@ -386,6 +552,69 @@ pub trait CollectionModel<P: PartialEq + Into<Bson> + Clone, T: CollectionModelC
             .ok()
     }
 
+    /// find_page returns up to `limit` documents matching `filter`, sorted on the integer
+    /// `field` then on `_id`, both descending. Without a `bound`, the page starts at the
+    /// first document, otherwise it is read from the documents before or after the cursor.
+    /// The cursor bounds `field` then `_id`, the way documents are sorted, so the query
+    /// is served by an index on the filtered fields followed by `field` and `_id`.
+    async fn find_page(
+        &self,
+        filter: Document,
+        field: &str,
+        bound: Option<PageBound>,
+        limit: i64,
+    ) -> Result<Page<T>, Error> {
+        let (filter, order) = match &bound {
+            None => (filter, SortOrder::DESC),
+            Some(PageBound::Before(cursor)) => (
+                doc! {"$and": [filter, doc! {"$or": [
+                    {field: {"$lt": cursor.value}},
+                    {field: cursor.value, "_id": {"$lt": cursor.id}},
+                ]}]},
+                SortOrder::DESC,
+            ),
+            Some(PageBound::After(cursor)) => (
+                doc! {"$and": [filter, doc! {"$or": [
+                    {field: {"$gt": cursor.value}},
+                    {field: cursor.value, "_id": {"$gt": cursor.id}},
+                ]}]},
+                SortOrder::ASC,
+            ),
+        };
+        let find_options = FindOptions::builder()
+            .sort(doc! {field: order.value(), "_id": order.value()})
+            // one more document than asked tells if there are more
+            .limit(limit + 1)
+            .build();
+        let mut documents: Vec<Document> = self
+            .collection()
+            .clone_with_type::<Document>()
+            .find(filter, find_options)
+            .await?
+            .try_collect()
+            .await?;
+
+        let has_more = documents.len() as i64 > limit;
+        documents.truncate(limit.max(0) as usize);
+        if let SortOrder::ASC = order {
+            documents.reverse();
+        }
+        Ok(Page {
+            first: documents
+                .first()
+                .and_then(|document| PageCursor::from_document(document, field)),
+            last: documents
+                .last()
+                .and_then(|document| PageCursor::from_document(document, field)),
+            items: documents
+                .into_iter()
+                .map(mongodb::bson::from_document::<T>)
+                .collect::<Result<Vec<T>, _>>()
+                .map_err(|err| Error(err.to_string()))?,
+            has_more,
+        })
+    }
+
     fn collection(&self) -> &Collection<T>;
     fn get_collection_name(&self) -> String;
     fn get_database(&self) -> Option<&Database>;
@@ -481,7 +710,7 @@ impl<'a, T: CollectionModelConstraint<String>> BlankCollection<'a, T> {
 mod tests {
     use std::collections::HashMap;
 
-    use super::{BlankCollection, FieldSort, PrimaryID};
+    use super::{BlankCollection, FieldSort, PageBound, PageCursor, PrimaryID};
     use crate::{
         config,
         db::{
@@ -491,6 +720,7 @@ mod tests {
         },
         entities::potential_articles::PotentialArticle,
     };
+    use mongodb::bson::{doc, oid::ObjectId};
     use serde::{Deserialize, Serialize};
 
     #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -509,6 +739,34 @@ mod tests {
         }
     }
 
+    #[derive(Clone, Debug, Serialize, Deserialize)]
+    struct Dated {
+        n: i32,
+        create_date: i64,
+    }
+    impl FieldSort<String> for Dated {
+        fn sort_by_value(&self) -> String {
+            self.n.to_string()
+        }
+    }
+
+    impl PrimaryID<String> for Dated {
+        fn get_primary_id(&self) -> Option<String> {
+            None
+        }
+    }
+
+    #[test]
+    fn test_i_can_encode_page_cursors() {
+        let cursor = PageCursor {
+            value: 1055217600000,
+            id: ObjectId::parse_str("65f1c2a4e13823a7c4b0f3d1").unwrap(),
+        };
+        assert_eq!(PageCursor::decode(&cursor.encode()).unwrap(), cursor);
+        assert!(PageCursor::decode("not a cursor").is_err());
+        assert!(PageCursor::decode(&hex::encode("1055217600000")).is_err());
+    }
+
     #[rocket::async_test]
     async fn test_get_seq() {
         let settings = config::Settings::new().unwrap();
@@ -540,4 +798,45 @@ mod tests {
             .unwrap()
         );
     }
+
+    #[rocket::async_test]
+    async fn test_find_page() {
+        let settings = config::Settings::new().unwrap();
+        let db_handle = db::mongo::get_handle(&settings).await;
+        let coll = BlankCollection::<Dated>::new(&db_handle, "panya", "test_find_page").unwrap();
+        coll.collection().delete_many(doc! {}, None).await.unwrap();
+        // most documents share their create_date, their _id breaking the ties
+        let dated: Vec<Dated> = (0..7)
+            .map(|n| Dated {
+                n,
+                create_date: if n < 5 { 1000 } else { 2000 },
+            })
+            .collect();
+        coll.insert_many(&dated).await.unwrap();
+
+        let mut seen = vec![];
+        let mut bound = None;
+        let last = loop {
+            let page = coll
+                .find_page(doc! {}, "create_date", bound, 2)
+                .await
+                .unwrap();
+            seen.extend(page.items.iter().map(|dated| dated.n));
+            if !page.has_more {
+                break page.last.unwrap();
+            }
+            bound = page.last.map(PageBound::Before);
+        };
+        assert_eq!(seen, vec![6, 5, 4, 3, 2, 1, 0]);
+
+        let page = coll
+            .find_page(doc! {}, "create_date", Some(PageBound::After(last)), 3)
+            .await
+            .unwrap();
+        assert_eq!(
+            page.items.iter().map(|dated| dated.n).collect::<Vec<i32>>(),
+            vec![3, 2, 1]
+        );
+        assert!(page.has_more);
+    }
 }
@@ -1,10 +1,14 @@
-use crate::error::Error;
+use crate::{entities::user::User, error::Error};
 
 use super::{
     model::{CollectionModel, CollectionModelConstraint},
     mongo::Handle,
 };
-use mongodb::{Collection, Database};
+use mongodb::{
+    bson::{doc, to_bson},
+    options::{IndexOptions, UpdateOptions},
+    Collection, Database, IndexModel,
+};
 use serde::Serialize;
 use std::fmt::Debug;
 
@@ -15,6 +19,21 @@ pub struct Users<'a, T: Serialize> {
     db_name: String,
 }
 impl<'a, T: CollectionModelConstraint<i32>> Users<'a, T> {
+    /// create_indexes is meant to be called once, at startup.
+    pub async fn create_indexes(&self) -> Result<(), Error> {
+        self.collection()
+            .create_index(
+                IndexModel::builder()
+                    .keys(doc! {"feed_token_hash": 1})
+                    .options(IndexOptions::builder().sparse(true).build())
+                    .build(),
+                None,
+            )
+            .await
+            .map(|_| ())
+            .map_err(Error::from)
+    }
+
     pub fn new(handle: &'a Handle, db_name: &'a str) -> Result<Self, Error> {
         let collection = (match handle.database(db_name) {
             Some(res) => res,
@@ -31,6 +50,75 @@ impl<'a, T: CollectionModelConstraint<i32>> Users<'a, T> {
     //     self.find_one("id", user.id).await
     // }
 }
+impl<'a> Users<'a, User> {
+    /// replace_channel subscribes the users of the channel `old` to the channel `new` instead,
+    /// in their folders alike, returning the number of users updated.
+    /// Only the channel ids are updated, so concurrent writes to the users are kept.
+    pub async fn replace_channel(&self, old: i32, new: i32) -> Result<usize, Error> {
+        self.collection()
+            .update_many(
+                doc! {"channel_ids": old},
+                doc! {"$addToSet": {"channel_ids": new}},
+                None,
+            )
+            .await?;
+        self.collection()
+            .update_many(
+                doc! {"folders.channel_ids": old},
+                doc! {"$addToSet": {"folders.$[folder].channel_ids": new}},
+                UpdateOptions::builder()
+                    .array_filters(vec![doc! {"folder.channel_ids": old}])
+                    .build(),
+            )
+            .await?;
+        let res = self
+            .collection()
+            .update_many(
+                doc! {"$or": [{"channel_ids": old}, {"folders.channel_ids": old}]},
+                doc! {"$pull": {"channel_ids": old, "folders.$[].channel_ids": old}},
+                None,
+            )
+            .await?;
+        Ok(res.modified_count as usize)
+    }
+
+    /// set_subscriptions stores the channels and folders of the user, leaving
+    /// the rest of the document, such as its feed token, as it is in the database.
+    pub async fn set_subscriptions(&self, user: &User) -> Result<(), Error> {
+        let folders = to_bson(&user.folders).map_err(|err| Error(err.to_string()))?;
+        self.collection()
+            .update_one(
+                doc! {"id": user.id},
+                doc! {"$set": {"channel_ids": user.channel_ids.clone(), "folders": folders}},
+                None,
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// find_by_feed_token returns the user whose feed token hashes into `token_hash`
+    pub async fn find_by_feed_token(&self, token_hash: &str) -> Option<User> {
+        self.find_by_field("feed_token_hash", token_hash.to_string())
+            .await
+    }
+
+    /// set_feed_token replaces the hash of the user's feed token, `None` revoking it
+    pub async fn set_feed_token(
+        &self,
+        user_id: i32,
+        token_hash: Option<&str>,
+    ) -> Result<(), Error> {
+        let update = match token_hash {
+            Some(token_hash) => doc! {"$set": {"feed_token_hash": token_hash}},
+            None => doc! {"$unset": {"feed_token_hash": ""}},
+        };
+        self.collection()
+            .update_one(doc! {"id": user_id}, update, None)
+            .await?;
+        Ok(())
+    }
+}
+
 impl<'a, P: PartialEq + Into<mongodb::bson::Bson> + Clone, T: CollectionModelConstraint<P>>
     CollectionModel<P, T> for Users<'a, T>
 {
@@ -10,12 +10,34 @@ use crate::{
         model::{CollectionModel, FieldSort, PrimaryID},
     },
     error::Error,
+    services::sources::registry,
 };
 
 #[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
 pub enum SourceType {
     RSSFeed,
+    AtomFeed,
+    JsonFeed,
     Bakery,
+    // a source type added through the sources registry, by name
+    Other(&'static str),
+}
+
+impl SourceType {
+    // channels scraped by panya itself, following their scraper rules
+    pub const SCRAPER: SourceType = SourceType::Other("scraper");
+    // JSON APIs, read following the channel's JSON API mapping
+    pub const JSON_API: SourceType = SourceType::Other("json_api");
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SourceType::RSSFeed => "rss_feed",
+            SourceType::AtomFeed => "atom_feed",
+            SourceType::JsonFeed => "json_feed",
+            SourceType::Bakery => "bakery",
+            SourceType::Other(name) => name,
+        }
+    }
 }
 
 impl Serialize for SourceType {
@@ -23,10 +45,7 @@ impl Serialize for SourceType {
     where
         S: serde::Serializer,
     {
-        serializer.serialize_str(match self {
-            SourceType::RSSFeed => "rss_feed",
-            SourceType::Bakery => "bakery",
-        })
+        serializer.serialize_str(self.as_str())
     }
 }
 
@@ -41,7 +60,9 @@ impl<'de> Deserialize<'de> for SourceType {
             type Value = SourceType;
 
             fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
-                formatter.write_str("'rss_feed', 'bakery' or 'other'")
+                formatter.write_str(
+                    "'rss_feed', 'atom_feed', 'json_feed', 'bakery' or a registered source type",
+                )
             }
 
             fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
@@ -50,11 +71,12 @@ impl<'de> Deserialize<'de> for SourceType {
             {
                 match v {
                     "rss_feed" => Ok(SourceType::RSSFeed),
+                    "atom_feed" => Ok(SourceType::AtomFeed),
+                    "json_feed" => Ok(SourceType::JsonFeed),
                     "bakery" => Ok(SourceType::Bakery),
-                    _ => Err(de::Error::unknown_variant(
-                        v,
-                        &["rss_feed", "bakery", "other"],
-                    )),
+                    _ => registry()
+                        .find(v)
+                        .ok_or_else(|| de::Error::invalid_value(de::Unexpected::Str(v), &self)),
                 }
             }
         }
@@ -73,6 +95,153 @@ pub struct Channel {
     pub base_refresh_frequency: Option<i32>,
     pub source_type: SourceType,
     pub weight: f32,
+    #[serde(default)]
+    pub etag: Option<String>,
+    #[serde(default)]
+    pub last_modified: Option<String>,
+    // count of 304 responses to conditional fetches
+    #[serde(default)]
+    pub not_modified_count: i64,
+    // count of 200 responses to conditional fetches
+    #[serde(default)]
+    pub modified_count: i64,
+    #[serde(default)]
+    pub health: ChannelHealth,
+    // the main content of new items is extracted from their page
+    #[serde(default)]
+    pub extract_content: bool,
+    #[serde(default)]
+    pub redirect: Option<Redirect>,
+    #[serde(default)]
+    pub websub: Option<WebSubSubscription>,
+    #[serde(default)]
+    pub scraper: Option<ScraperRules>,
+    #[serde(default)]
+    pub json_api: Option<JsonApiMapping>,
+}
+
+/// JsonApiMapping tells where the items of a JSON API's response are, and where their fields
+/// are within each item. Locations are JSON pointers, such as "/data/items", or dotted paths,
+/// such as "data.items". The items location is relative to the document, the others to an item.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, PartialOrd)]
+pub struct JsonApiMapping {
+    // empty when the document is the array of items
+    #[serde(default)]
+    pub items: String,
+    pub link: String,
+    #[serde(default)]
+    pub title: Option<String>,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub image: Option<String>,
+    // a string date, or a timestamp in seconds or milliseconds
+    #[serde(default)]
+    pub date: Option<String>,
+    // chrono format of string dates. RFC 2822 and RFC 3339 dates are read without one.
+    #[serde(default)]
+    pub date_format: Option<String>,
+    // an array of strings, or a single string
+    #[serde(default)]
+    pub categories: Option<String>,
+}
+
+/// ScraperRules are the CSS selectors a scraper channel's page is read with.
+/// The item selector matches each item's container, the others are looked up within it.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, PartialOrd)]
+pub struct ScraperRules {
+    pub item: String,
+    // the item's link is the href of the matched element, or of the first link within it.
+    // Without a selector, the item's container is used.
+    #[serde(default)]
+    pub link: Option<String>,
+    // defaults to the text of the link
+    #[serde(default)]
+    pub title: Option<String>,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub image: Option<String>,
+    #[serde(default)]
+    pub date: Option<String>,
+    // chrono format of the date, such as "%d/%m/%Y". RFC 2822 and RFC 3339 dates are read without one.
+    #[serde(default)]
+    pub date_format: Option<String>,
+}
+
+// s, a subscription the hub did not verify within this delay is requested again
+pub const WEBSUB_PENDING_TIMEOUT: i64 = 86400;
+
+/// WebSubSubscription is a push subscription to the channel's topic on a WebSub hub
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, PartialOrd)]
+pub struct WebSubSubscription {
+    pub hub: String,
+    pub topic: String,
+    // the hub verified the subscription
+    pub verified: bool,
+    // a subscription request awaits the hub's verification
+    #[serde(default)]
+    pub pending: bool,
+    // s, when the subscription was last requested
+    pub requested_at: i64,
+    // s, lease granted by the hub, and its end
+    pub lease_seconds: Option<i64>,
+    pub lease_expires: Option<i64>,
+}
+
+impl WebSubSubscription {
+    /// is_active tells if the hub pushes the channel's updates at `now` (s)
+    pub fn is_active(&self, now: i64) -> bool {
+        self.verified
+            && self
+                .lease_expires
+                .is_some_and(|lease_expires| lease_expires > now)
+    }
+
+    /// awaits_verification tells if the hub may verify the subscription at `now` (s):
+    /// it was requested within the last WEBSUB_PENDING_TIMEOUT, and not verified since.
+    pub fn awaits_verification(&self, now: i64) -> bool {
+        self.pending && now - self.requested_at <= WEBSUB_PENDING_TIMEOUT
+    }
+
+    /// needs_renewal tells if less than a tenth of the lease remains at `now` (s)
+    pub fn needs_renewal(&self, now: i64) -> bool {
+        match (self.lease_seconds, self.lease_expires) {
+            (Some(lease_seconds), Some(lease_expires)) => lease_expires - now <= lease_seconds / 10,
+            _ => false,
+        }
+    }
+}
+
+/// Redirect is a permanent redirect of the channel's url, observed
+/// `observations` times in a row before the channel is moved to `location`.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, PartialOrd)]
+pub struct Redirect {
+    pub location: String,
+    pub observations: i32,
+}
+
+/// ChannelHealth keeps track of a channel's failing fetches
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq, PartialOrd)]
+pub struct ChannelHealth {
+    pub consecutive_failures: i32,
+    pub last_error: Option<String>,
+    pub last_http_status: Option<i32>,
+    // s, the channel is not fetched before then
+    pub next_retry: Option<i64>,
+    pub suspended: bool,
+}
+
+impl ChannelHealth {
+    pub fn is_healthy(&self) -> bool {
+        *self == ChannelHealth::default()
+    }
+
+    /// is_ready tells if a channel may be fetched at `now` (s),
+    /// meaning it is neither suspended nor backing off.
+    pub fn is_ready(&self, now: i64) -> bool {
+        !self.suspended && self.next_retry.is_none_or(|next_retry| next_retry <= now)
+    }
 }
 
 impl PrimaryID<i32> for Channel {
@@ -99,8 +268,28 @@ impl Channel {
             base_refresh_frequency: Some(base_refresh_frequency),
             source_type: source,
             weight: 1.,
+            etag: None,
+            last_modified: None,
+            not_modified_count: 0,
+            modified_count: 0,
+            health: ChannelHealth::default(),
+            extract_content: false,
+            redirect: None,
+            websub: None,
+            scraper: None,
+            json_api: None,
         }
     }
+
+    /// is_due tells if `refresh_frequency` (ms) has elapsed since `last_refresh` (s)
+    pub fn is_due(&self, now_ms: i64) -> bool {
+        self.is_due_every(self.refresh_frequency, now_ms)
+    }
+
+    /// is_due_every tells if `frequency` (ms) has elapsed since `last_refresh` (s)
+    pub fn is_due_every(&self, frequency: i32, now_ms: i64) -> bool {
+        self.last_refresh * 1000 + frequency as i64 <= now_ms
+    }
 }
 
 pub async fn new_with_seq_db(
@@ -110,7 +299,15 @@ pub async fn new_with_seq_db(
     channels_coll: &Channels<'_, Channel>,
     settings: &Settings,
 ) -> Result<Channel, Error> {
-    let mut channel = Channel::new(name, url, source, settings.base_refresh_frequency);
+    let channel = Channel::new(name, url, source, settings.base_refresh_frequency);
+    insert_with_seq_db(channel, channels_coll).await
+}
+
+/// insert_with_seq_db stores a channel under the next id of the sequence
+pub async fn insert_with_seq_db(
+    mut channel: Channel,
+    channels_coll: &Channels<'_, Channel>,
+) -> Result<Channel, Error> {
     channel.id = channels_coll.get_next_seq().await?;
     channels_coll
         .insert_many(&[channel.clone()], Some("id".to_string()))
@@ -32,6 +32,8 @@ mod test {
             id: 1,
             username: "salut@test.com".to_string(),
             channel_ids: vec![],
+            folders: vec![],
+            feed_token_hash: None,
         };
         assert_eq!(true, UserConstraints(&user1).assert().unwrap());
 
@@ -39,6 +41,8 @@ mod test {
             id: 2,
             username: "salut".to_string(),
             channel_ids: vec![],
+            folders: vec![],
+            feed_token_hash: None,
         };
         assert_eq!(false, UserConstraints(&user2).assert().unwrap());
     }
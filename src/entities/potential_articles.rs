@@ -1,8 +1,37 @@
-use chrono::{TimeZone, Utc};
+use chrono::{DateTime, TimeZone, Utc};
+use mongodb::bson::{to_document, Document};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use url::Url;
 
 use crate::db::model::{FieldSort, PrimaryID};
+use crate::error::Error;
+use crate::services::link_op::canonical_link;
 
+/// item_key returns a stable key identifying an item within its channel:
+/// the hash of its guid if it has one, of its canonicalized link otherwise.
+pub fn item_key(guid: Option<&str>, link: &str) -> String {
+    let source = match guid.map(str::trim).filter(|guid| !guid.is_empty()) {
+        Some(guid) => guid.to_string(),
+        None => canonical_link(link),
+    };
+    format!("{:x}", Sha256::digest(source.as_bytes()))
+}
+
+/// Enclosure is a media file attached to an item, such as a podcast episode
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq, PartialOrd)]
+pub struct Enclosure {
+    pub url: String,
+    pub mime_type: String,
+    // bytes
+    pub length: i64,
+    // as given by itunes:duration, either seconds or [hh:]mm:ss
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration: Option<String>,
+}
+
+// serialized field names are meant for the API,
+// the items collection uses the struct's field names, see StoredArticle.
 #[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq, PartialOrd)]
 pub struct PotentialArticle {
     pub link: String,
@@ -17,9 +46,69 @@ pub struct PotentialArticle {
     // #[serde(skip_serializing)]
     pub channel_id: Option<i32>,
     pub categories: Option<Vec<String>>,
+    #[serde(default)]
+    pub key: String,
+    // main content of the article's page, when the channel extracts it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content_html: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content_text: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub enclosure: Option<Enclosure>,
+}
+
+/// StoredArticle is an article as stored in the items collection,
+/// under the field names PotentialArticle is deserialized from.
+#[derive(Serialize)]
+struct StoredArticle<'a> {
+    link: &'a str,
+    img: &'a str,
+    title: &'a Option<String>,
+    desc: &'a str,
+    create_date: i64,
+    channel_name: &'a Option<String>,
+    channel_id: Option<i32>,
+    categories: &'a Option<Vec<String>>,
+    key: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    content_html: &'a Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    content_text: &'a Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    enclosure: &'a Option<Enclosure>,
+}
+
+impl<'a> From<&'a PotentialArticle> for StoredArticle<'a> {
+    fn from(article: &'a PotentialArticle) -> Self {
+        StoredArticle {
+            link: &article.link,
+            img: &article.img,
+            title: &article.title,
+            desc: &article.desc,
+            create_date: article.create_date,
+            channel_name: &article.channel_name,
+            channel_id: article.channel_id,
+            categories: &article.categories,
+            key: &article.key,
+            content_html: &article.content_html,
+            content_text: &article.content_text,
+            enclosure: &article.enclosure,
+        }
+    }
 }
 
 impl PotentialArticle {
+    /// to_db_document serializes the article with the field names of the items collection
+    pub fn to_db_document(&self) -> Result<Document, Error> {
+        to_document(&StoredArticle::from(self)).map_err(|err| Error(err.to_string()))
+    }
+
+    /// with_key sets the item key, from `guid` if any or from the link
+    pub fn with_key(mut self, guid: Option<&str>) -> Self {
+        self.key = item_key(guid, &self.link);
+        self
+    }
+
     pub fn some_link(&self) -> Option<String> {
         Some(self.link.clone())
     }
@@ -50,6 +139,22 @@ impl PotentialArticle {
     pub fn some_human_date(&self) -> Option<String> {
         Some(self.human_date())
     }
+
+    /// guid returns a globally unique id for the article: its link when it is an
+    /// absolute url, a urn made of its key otherwise.
+    pub fn guid(&self) -> String {
+        match Url::parse(&self.link) {
+            Ok(url) => url.to_string(),
+            Err(_) => format!("urn:panya:item:{}", self.key),
+        }
+    }
+
+    /// date_time returns the article's date, `create_date` being in ms
+    pub fn date_time(&self) -> DateTime<Utc> {
+        Utc.timestamp_millis_opt(self.create_date)
+            .single()
+            .unwrap_or_else(Utc::now)
+    }
 }
 
 impl Ord for PotentialArticle {
@@ -69,3 +174,64 @@ impl FieldSort<String> for PotentialArticle {
         self.link.clone()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_i_can_serialize_for_db() {
+        let article = PotentialArticle {
+            link: "https://example.com/a".to_string(),
+            img: String::new(),
+            title: None,
+            desc: "desc".to_string(),
+            create_date: 1055217600000,
+            channel_name: Some("example.com".to_string()),
+            channel_id: Some(1),
+            categories: None,
+            key: String::new(),
+            content_html: None,
+            content_text: None,
+            enclosure: Some(Enclosure {
+                url: "https://example.com/a.mp3".to_string(),
+                mime_type: "audio/mpeg".to_string(),
+                length: 1000,
+                duration: None,
+            }),
+        };
+        let document = article.to_db_document().unwrap();
+
+        assert_eq!(document.get_str("desc").unwrap(), "desc");
+        assert_eq!(document.get_i64("create_date").unwrap(), 1055217600000);
+        assert_eq!(document.get_str("channel_name").unwrap(), "example.com");
+        assert!(document.get("pubDate").is_none());
+        assert!(document.get("description").is_none());
+        assert!(document.get("channelTitle").is_none());
+        assert_eq!(
+            mongodb::bson::from_document::<PotentialArticle>(document).unwrap(),
+            article
+        );
+    }
+
+    #[test]
+    fn test_i_can_compute_item_keys() {
+        assert_eq!(
+            item_key(None, "https://www.example.com/a?utm_source=rss"),
+            item_key(None, "http://example.com/a/"),
+        );
+        assert_ne!(
+            item_key(None, "https://example.com/a"),
+            item_key(None, "https://example.com/b"),
+        );
+        assert_eq!(
+            item_key(Some("tag:example.com,2003:1"), "https://example.com/a"),
+            item_key(Some("tag:example.com,2003:1"), "https://example.com/b"),
+        );
+        assert_eq!(
+            item_key(Some(" "), "https://example.com/a"),
+            item_key(None, "https://example.com/a"),
+        );
+        assert_eq!(item_key(None, "https://example.com/a").len(), 64);
+    }
+}
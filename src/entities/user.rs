@@ -19,6 +19,18 @@ pub struct User {
     pub username: String,
     #[serde(default)]
     pub channel_ids: Vec<i32>,
+    #[serde(default)]
+    pub folders: Vec<Folder>,
+    // sha256 of the secret token of the user's public feed url, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub feed_token_hash: Option<String>,
+}
+
+/// Folder groups some of a user's channels, as OPML outlines do
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, PartialOrd)]
+pub struct Folder {
+    pub name: String,
+    pub channel_ids: Vec<i32>,
 }
 
 #[rocket::async_trait]
@@ -65,6 +77,23 @@ impl<'r> FromRequest<'r> for User {
     }
 }
 
+impl User {
+    /// file_channel moves a channel into the folder `name`, creating it if needed
+    pub fn file_channel(&mut self, channel_id: i32, name: &str) {
+        for folder in self.folders.iter_mut() {
+            folder.channel_ids.retain(|id| *id != channel_id);
+        }
+        match self.folders.iter_mut().find(|folder| folder.name == name) {
+            Some(folder) => folder.channel_ids.push(channel_id),
+            None => self.folders.push(Folder {
+                name: name.to_string(),
+                channel_ids: vec![channel_id],
+            }),
+        }
+        self.folders.retain(|folder| !folder.channel_ids.is_empty());
+    }
+}
+
 impl FieldSort<String> for User {
     fn sort_by_value(&self) -> String {
         self.id.to_string()
@@ -88,3 +117,35 @@ impl CloneEntity<User> for User {
         (*self).clone()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_i_can_file_channels() {
+        let mut user = User {
+            id: 1,
+            username: "salut@test.com".to_string(),
+            channel_ids: vec![1, 2, 3],
+            folders: vec![],
+            feed_token_hash: None,
+        };
+        user.file_channel(1, "News");
+        user.file_channel(2, "News");
+        user.file_channel(1, "Tech");
+        assert_eq!(
+            user.folders,
+            vec![
+                Folder {
+                    name: "News".to_string(),
+                    channel_ids: vec![2],
+                },
+                Folder {
+                    name: "Tech".to_string(),
+                    channel_ids: vec![1],
+                },
+            ]
+        );
+    }
+}
@@ -65,9 +65,35 @@ impl From<regex::Error> for Error {
     }
 }
 
+impl From<rss::Error> for Error {
+    fn from(value: rss::Error) -> Self {
+        Error(value.to_string())
+    }
+}
+
+impl From<atom_syndication::Error> for Error {
+    fn from(value: atom_syndication::Error) -> Self {
+        Error(value.to_string())
+    }
+}
+
+impl From<quick_xml::Error> for Error {
+    fn from(value: quick_xml::Error) -> Self {
+        Error(value.to_string())
+    }
+}
+
+impl From<quick_xml::events::attributes::AttrError> for Error {
+    fn from(value: quick_xml::events::attributes::AttrError) -> Self {
+        Error(value.to_string())
+    }
+}
+
 pub enum HTTPError {
     BadRequest(Error),
     Unauthorized(Error),
+    NotFound(Error),
+    TooManyRequests(Error),
     InternalServerError(Error),
 }
 
@@ -76,6 +102,8 @@ impl HTTPError {
         match self {
             HTTPError::BadRequest(_) => Status::BadRequest,
             HTTPError::Unauthorized(_) => Status::Unauthorized,
+            HTTPError::NotFound(_) => Status::NotFound,
+            HTTPError::TooManyRequests(_) => Status::TooManyRequests,
             HTTPError::InternalServerError(_) => Status::InternalServerError,
             // _ => Status::BadRequest,
         }
@@ -87,6 +115,8 @@ impl HTTPError {
             match self {
                 HTTPError::BadRequest(v) => v.clone(),
                 HTTPError::Unauthorized(v) => v.clone(),
+                HTTPError::NotFound(v) => v.clone(),
+                HTTPError::TooManyRequests(v) => v.clone(),
                 HTTPError::InternalServerError(v) => v.clone(),
             },
             self.get_http_status().code,
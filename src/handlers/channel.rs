@@ -1,16 +1,28 @@
 use std::sync::Arc;
 
 use crate::config::Settings;
+use crate::converters::json_api::validate_mapping;
+use crate::converters::scraper::validate_rules;
 use crate::db::model::Updatable;
 use crate::db::user::Users;
-use crate::entities::channel::{new_with_seq_db, Channel, SourceType};
+use crate::entities::channel::{
+    insert_with_seq_db, Channel, ChannelHealth, JsonApiMapping, ScraperRules, SourceType,
+};
+use crate::entities::potential_articles::PotentialArticle;
 use crate::entities::user::User;
 use crate::error::{Error, HTTPError};
 use crate::request_guards::auth::Auth;
 use crate::request_guards::xqueryid::XQueryID;
-use crate::services::channels::find_out_source_type;
+use crate::services::channels::{resolve_source, SourceResolution};
+use crate::services::discovery::FeedCandidate;
 use crate::services::link_op::trim_link;
+use crate::services::refresh::refresh_channel;
+use crate::services::scheduler::FetchScheduler;
+use crate::services::sources::preview_source;
+use crate::utils::now_timestamp_ms;
+use chrono::Utc;
 use mongodb::bson::doc;
+use rocket::http::Status;
 use rocket::serde::json::Json;
 use serde::{Deserialize, Serialize};
 
@@ -31,6 +43,15 @@ pub struct AddChannel {
     channel_id: i32,
     #[serde(skip_serializing_if = "Option::is_none")]
     refresh_frequency: Option<i32>,
+    // rules of a scraper channel
+    #[serde(default, skip_serializing)]
+    scraper: Option<ScraperRules>,
+    // mapping of a json_api channel
+    #[serde(default, skip_serializing)]
+    json_api: Option<JsonApiMapping>,
+    // feeds advertised by the page, when none could be picked
+    #[serde(skip_deserializing, skip_serializing_if = "Vec::is_empty")]
+    candidates: Vec<FeedCandidate>,
 }
 
 #[derive(Deserialize, Serialize)]
@@ -41,6 +62,63 @@ pub struct UpdateChannel {
     source_type: Option<SourceType>,
     #[serde(skip_serializing_if = "Option::is_none")]
     refresh_frequency: Option<i32>,
+    // false resumes a suspended channel, clearing its failures
+    #[serde(skip_serializing_if = "Option::is_none")]
+    suspended: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    extract_content: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    scraper: Option<ScraperRules>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    json_api: Option<JsonApiMapping>,
+}
+
+#[derive(Deserialize)]
+pub struct ScraperPreview {
+    url: String,
+    rules: ScraperRules,
+}
+
+#[derive(Deserialize)]
+pub struct JsonApiPreview {
+    url: String,
+    mapping: JsonApiMapping,
+}
+
+#[derive(Serialize)]
+pub struct ChannelHealthReport {
+    id: i32,
+    name: String,
+    url: String,
+    last_refresh: i64,
+    last_successful_refresh: Option<i64>,
+    #[serde(flatten)]
+    health: ChannelHealth,
+}
+
+impl From<Channel> for ChannelHealthReport {
+    fn from(channel: Channel) -> Self {
+        ChannelHealthReport {
+            id: channel.id,
+            name: channel.name,
+            url: channel.url,
+            last_refresh: channel.last_refresh,
+            last_successful_refresh: channel.last_successful_refresh,
+            health: channel.health,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct RefreshChannelSummary {
+    channel_id: i32,
+    fetched: usize,
+    inserted: usize,
+    updated: usize,
+    not_modified: bool,
+    duration_ms: u128,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
 }
 
 impl Updatable<i32, Channel> for UpdateChannel {
@@ -56,10 +134,40 @@ impl Updatable<i32, Channel> for UpdateChannel {
         if let Some(name) = &self.name {
             new.name = name.clone();
         }
+        if let Some(extract_content) = self.extract_content {
+            new.extract_content = extract_content;
+        }
+        if let Some(scraper) = &self.scraper {
+            new.scraper = Some(scraper.clone());
+        }
+        if let Some(json_api) = &self.json_api {
+            new.json_api = Some(json_api.clone());
+        }
+        match self.suspended {
+            Some(false) => new.health = ChannelHealth::default(),
+            Some(true) => new.health.suspended = true,
+            None => {}
+        }
         new
     }
 }
 
+/// check_source_rules refuses channels whose source cannot be read without rules,
+/// such as scraper channels without their scraper rules, or json_api ones without their mapping.
+fn check_source_rules(channel: &Channel) -> Result<(), HTTPError> {
+    if channel.source_type == SourceType::SCRAPER && channel.scraper.is_none() {
+        return Err(HTTPError::BadRequest(Error::str(
+            "scraper channels need scraper rules",
+        )));
+    }
+    if channel.source_type == SourceType::JSON_API && channel.json_api.is_none() {
+        return Err(HTTPError::BadRequest(Error::str(
+            "json_api channels need a json_api mapping",
+        )));
+    }
+    Ok(())
+}
+
 #[get("/channels")]
 pub async fn get_channel_list(
     db_handle: &rocket::State<Arc<Handle>>,
@@ -77,33 +185,71 @@ pub async fn get_channel_list(
 }
 
 // /panya/channel
+// Answers 300 with the candidate feeds when the page advertises several of them.
 #[post("/channel", format = "json", data = "<add_channel>")]
 pub async fn add_url(
     handle: &rocket::State<Arc<Handle>>,
+    scheduler: &rocket::State<Arc<FetchScheduler>>,
     add_channel: Json<AddChannel>,
     settings: &rocket::State<Settings>,
     uuid: XQueryID,
     mut user: User,
-) -> Result<Json<AddChannel>, HTTPError> {
+) -> Result<(Status, Json<AddChannel>), HTTPError> {
     let channels_coll =
         Channels::new(handle, "panya").map_err(|err| HTTPError::InternalServerError(err))?;
     let users_coll =
         Users::<User>::new(handle, "panya").map_err(|err| HTTPError::InternalServerError(err))?;
-    let channel_name = trim_link(&add_channel.channel_url);
+    if let Some(rules) = &add_channel.scraper {
+        validate_rules(rules).map_err(HTTPError::BadRequest)?;
+    }
+    if let Some(mapping) = &add_channel.json_api {
+        validate_mapping(mapping).map_err(HTTPError::BadRequest)?;
+    }
+    let mut channel_name = trim_link(&add_channel.channel_url);
     let mut channel_opt = channels_coll.find_one("name", &channel_name).await;
-    let url = &add_channel.channel_url;
-    let source_type = match add_channel.source_type {
-        Some(res) => res,
-        None => match find_out_source_type(url).await {
-            Ok(res) => res,
-            Err(err) => return Err(HTTPError::BadRequest(err)),
-        },
-    };
 
     if channel_opt.is_none() {
-        channel_opt = new_with_seq_db(&channel_name, url, source_type, &channels_coll, &settings)
-            .await
-            .ok();
+        let (url, source_type) = match add_channel.source_type {
+            Some(res) => (add_channel.channel_url.clone(), res),
+            None => match resolve_source(scheduler, &add_channel.channel_url).await {
+                Ok(SourceResolution::Found { url, source_type }) => (url, source_type),
+                Ok(SourceResolution::Ambiguous(candidates)) => {
+                    return Ok((
+                        Status::MultipleChoices,
+                        Json(AddChannel {
+                            channel_url: add_channel.channel_url.clone(),
+                            channel_name,
+                            source_type: None,
+                            channel_id: 0,
+                            refresh_frequency: None,
+                            scraper: None,
+                            json_api: None,
+                            candidates,
+                        }),
+                    ))
+                }
+                Err(err) => return Err(HTTPError::BadRequest(err)),
+            },
+        };
+        // the discovered feed may already be a channel
+        channel_name = trim_link(&url);
+        channel_opt = match channels_coll.find_one("name", &channel_name).await {
+            Some(res) => Some(res),
+            None => {
+                let channel = Channel {
+                    scraper: add_channel.scraper.clone(),
+                    json_api: add_channel.json_api.clone(),
+                    ..Channel::new(
+                        &channel_name,
+                        &url,
+                        source_type,
+                        settings.base_refresh_frequency,
+                    )
+                };
+                check_source_rules(&channel)?;
+                insert_with_seq_db(channel, &channels_coll).await.ok()
+            }
+        };
     }
 
     let c = channel_opt.ok_or_else(|| {
@@ -115,23 +261,23 @@ pub async fn add_url(
     if !user.channel_ids.contains(&c.id) {
         user.channel_ids.push(c.id);
     }
-    let res = users_coll.update_one_or_insert("id", user.id, &user).await;
-
-    if let Some(err) = &(res.1) {
+    if let Err(err) = users_coll.set_subscriptions(&user).await {
         eprintln!("({}) {}", uuid, err);
+        return Err(HTTPError::InternalServerError(err));
     }
-    if res.0 == false {
-        return Err(HTTPError::InternalServerError(
-            res.1.unwrap_or_else(|| Error::str("unknown error")),
-        ));
-    }
-    Ok(Json(AddChannel {
-        channel_url: c.url,
-        channel_name: c.name,
-        source_type: Some(c.source_type),
-        channel_id: c.id,
-        refresh_frequency: None,
-    }))
+    Ok((
+        Status::Ok,
+        Json(AddChannel {
+            channel_url: c.url,
+            channel_name: c.name,
+            source_type: Some(c.source_type),
+            channel_id: c.id,
+            refresh_frequency: None,
+            scraper: None,
+            json_api: None,
+            candidates: vec![],
+        }),
+    ))
 }
 
 // /panya/channel
@@ -142,11 +288,87 @@ pub async fn update_channel(
     update_channel: Json<UpdateChannel>,
     _uuid: XQueryID,
     _auth: Auth,
-) -> Result<Json<Channel>, Error> {
-    Channels::new(handle, "panya")?
+) -> Result<Json<Channel>, HTTPError> {
+    if let Some(rules) = &update_channel.scraper {
+        validate_rules(rules).map_err(HTTPError::BadRequest)?;
+    }
+    if let Some(mapping) = &update_channel.json_api {
+        validate_mapping(mapping).map_err(HTTPError::BadRequest)?;
+    }
+    let channels_coll = Channels::new(handle, "panya")?;
+    let channel = channels_coll
+        .find_one("id", id)
+        .await
+        .ok_or_else(|| HTTPError::BadRequest(Error::str("No channel found")))?;
+    check_source_rules(&update_channel.update(channel))?;
+    Ok(channels_coll
         .update_one("id", id, &update_channel.into_inner())
         .await
-        .map(|entity| Json(entity))
+        .map(Json)?)
+}
+
+// POST /panya/channel/scraper/preview
+// Scrapes a page with the given rules and returns its items, without storing anything.
+#[post("/channel/scraper/preview", format = "json", data = "<preview>")]
+pub async fn preview_scraper(
+    scheduler: &rocket::State<Arc<FetchScheduler>>,
+    settings: &rocket::State<Settings>,
+    preview: Json<ScraperPreview>,
+    _uuid: XQueryID,
+    _auth: Auth,
+) -> Result<Json<Vec<PotentialArticle>>, HTTPError> {
+    let preview = preview.into_inner();
+    validate_rules(&preview.rules).map_err(HTTPError::BadRequest)?;
+    let channel = Channel {
+        scraper: Some(preview.rules),
+        ..Channel::new(
+            &trim_link(&preview.url),
+            &preview.url,
+            SourceType::SCRAPER,
+            settings.base_refresh_frequency,
+        )
+    };
+    preview_source(scheduler, settings, &channel)
+        .await
+        .map(Json)
+        .map_err(|err| {
+            HTTPError::BadRequest(Error(format!(
+                "could not scrape {}: {}",
+                preview.url, err.error
+            )))
+        })
+}
+
+// POST /panya/channel/json_api/preview
+// Reads a JSON API's response with the given mapping and returns its items, without storing anything.
+#[post("/channel/json_api/preview", format = "json", data = "<preview>")]
+pub async fn preview_json_api(
+    scheduler: &rocket::State<Arc<FetchScheduler>>,
+    settings: &rocket::State<Settings>,
+    preview: Json<JsonApiPreview>,
+    _uuid: XQueryID,
+    _auth: Auth,
+) -> Result<Json<Vec<PotentialArticle>>, HTTPError> {
+    let preview = preview.into_inner();
+    validate_mapping(&preview.mapping).map_err(HTTPError::BadRequest)?;
+    let channel = Channel {
+        json_api: Some(preview.mapping),
+        ..Channel::new(
+            &trim_link(&preview.url),
+            &preview.url,
+            SourceType::JSON_API,
+            settings.base_refresh_frequency,
+        )
+    };
+    preview_source(scheduler, settings, &channel)
+        .await
+        .map(Json)
+        .map_err(|err| {
+            HTTPError::BadRequest(Error(format!(
+                "could not read {}: {}",
+                preview.url, err.error
+            )))
+        })
 }
 
 // /panya/channel
@@ -166,6 +388,97 @@ pub async fn get_channel(
     ))
 }
 
+// /panya/channel/<id>/health
+#[get("/channel/<id>/health")]
+pub async fn get_channel_health(
+    handle: &rocket::State<Arc<Handle>>,
+    id: i32,
+    _uuid: XQueryID,
+    _auth: Auth,
+) -> Result<Json<ChannelHealthReport>, Error> {
+    let channels_coll = Channels::new(handle, "panya")?;
+    Ok(Json(
+        channels_coll
+            .find_one("id", id)
+            .await
+            .ok_or(Error("No channel found".to_string()))?
+            .into(),
+    ))
+}
+
+// /panya/channel/<id>/refresh
+// Answers 429 when the channel was refreshed less than `manual_refresh_cooldown` ago,
+// 400 when it is suspended, and 502 with the error when its source could not be fetched.
+#[post("/channel/<id>/refresh")]
+pub async fn refresh_channel_now(
+    handle: &rocket::State<Arc<Handle>>,
+    scheduler: &rocket::State<Arc<FetchScheduler>>,
+    settings: &rocket::State<Settings>,
+    id: i32,
+    uuid: XQueryID,
+    _auth: Auth,
+) -> Result<(Status, Json<RefreshChannelSummary>), HTTPError> {
+    let channels_coll = Channels::new(handle, "panya")?;
+    let channel = channels_coll
+        .find_one("id", id)
+        .await
+        .ok_or_else(|| HTTPError::BadRequest(Error::str("No channel found")))?;
+    if channel.health.suspended {
+        return Err(HTTPError::BadRequest(Error::str(
+            "channel is suspended, resume it first",
+        )));
+    }
+    let retry_in = channel.last_refresh + settings.manual_refresh_cooldown - Utc::now().timestamp();
+    if retry_in > 0 {
+        return Err(HTTPError::TooManyRequests(Error(format!(
+            "channel was refreshed recently, retry in {}s",
+            retry_in
+        ))));
+    }
+    // the refresh is reserved before fetching, so concurrent calls do not all hit the source
+    if !channels_coll
+        .reserve_refresh(id, settings.manual_refresh_cooldown)
+        .await?
+    {
+        return Err(HTTPError::TooManyRequests(Error(format!(
+            "channel is being refreshed, retry in {}s",
+            settings.manual_refresh_cooldown
+        ))));
+    }
+
+    let time_before = now_timestamp_ms();
+    let res = refresh_channel(handle, scheduler, settings, &channel).await;
+    Ok(match res {
+        Ok(report) => (
+            Status::Ok,
+            Json(RefreshChannelSummary {
+                channel_id: id,
+                fetched: report.fetched,
+                inserted: report.inserted,
+                updated: report.updated,
+                not_modified: report.not_modified,
+                duration_ms: report.duration_ms,
+                error: None,
+            }),
+        ),
+        Err(err) => {
+            eprintln!("({}) could not refresh channel {}: {}", uuid, id, err);
+            (
+                Status::BadGateway,
+                Json(RefreshChannelSummary {
+                    channel_id: id,
+                    fetched: 0,
+                    inserted: 0,
+                    updated: 0,
+                    not_modified: false,
+                    duration_ms: now_timestamp_ms() - time_before,
+                    error: Some(err.to_string()),
+                }),
+            )
+        }
+    })
+}
+
 // /panya/channel
 #[delete("/channel/<id>")]
 pub async fn delete_channel(
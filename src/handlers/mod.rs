@@ -5,3 +5,4 @@ pub mod json;
 pub mod panya;
 pub mod public_entities;
 pub mod user;
+pub mod websub;
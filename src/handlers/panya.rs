@@ -6,70 +6,106 @@ use crate::db::channel::Channels;
 use crate::db::items::Items;
 use crate::db::model::CollectionModel;
 use crate::db::mongo::Handle;
-use crate::entities::channel::{new_with_seq_db, SourceType};
+use crate::entities::channel::{new_with_seq_db, Channel, SourceType};
 use crate::entities::potential_articles::PotentialArticle;
 use crate::request_guards::auth::Auth;
-use crate::request_guards::xqueryid::XQueryID;
-use crate::services::cook_rss::cook;
-use crate::services::panya::return_db_articles;
+use crate::request_guards::publicurl::PublicUrl;
+use crate::services::feed::{public_uri, FeedFormat};
+use crate::services::panya::{return_db_articles, trigger_bakery};
+use crate::services::scheduler::FetchScheduler;
+use crate::services::sources::registry;
 use crate::utils::clean_url;
 use mongodb::bson::doc;
-use rocket::response::content::RawXml;
+use rocket::http::{Accept, ContentType};
 use rocket::{error, warn};
 
 #[derive(FromForm)]
 pub struct GetUrlQuery {
     url: String,
     limit: Option<i64>,
+    // rss, atom or json, the Accept header deciding otherwise
+    format: Option<String>,
 }
 
-fn handle_error(err: &dyn StdError, msg: &str, url: &str) -> RawXml<String> {
-    error!("{}: {}", msg, err);
-    RawXml(cook(url, url, vec![]))
+/// FeedResponse cooks the feeds of get_url in the negotiated format
+struct FeedResponse<'a> {
+    format: FeedFormat,
+    url: &'a str,
+    self_url: String,
+}
+
+impl FeedResponse<'_> {
+    fn cook(
+        &self,
+        title: &str,
+        channels: &[Channel],
+        items: Vec<PotentialArticle>,
+    ) -> (ContentType, String) {
+        self.format
+            .cook(self.url, title, &self.self_url, None, channels, items)
+    }
+
+    fn error(&self, err: &dyn StdError, msg: &str) -> (ContentType, String) {
+        error!("{}: {}", msg, err);
+        self.cook(self.url, &[], vec![])
+    }
 }
 
 // /panya?url=
 #[get("/?<query..>")]
 pub async fn get_url(
     handle: &rocket::State<Arc<Handle>>,
+    scheduler: &rocket::State<Arc<FetchScheduler>>,
     settings: &rocket::State<Settings>,
     query: GetUrlQuery,
-    _uuid: XQueryID,
+    accept: Option<&Accept>,
+    public_url: PublicUrl<'_>,
     _auth: Auth,
-) -> RawXml<String> {
+) -> (ContentType, String) {
+    let url = &query.url;
+    let response = FeedResponse {
+        format: FeedFormat::negotiate(query.format.as_deref(), accept).unwrap_or(FeedFormat::Rss),
+        url,
+        self_url: public_uri(&public_url.base, public_url.origin),
+    };
     if query.url.is_empty() {
         warn!("handler::get_url - no url found");
-        return RawXml(cook(&query.url, &query.url, vec![]));
+        return response.cook(url, &[], vec![]);
     }
-    let url = &query.url;
     // println!("{:?}", request);
     let name = clean_url(&query.url).unwrap_or(query.url.clone());
     let limit = query.limit.unwrap_or(5);
     let items_coll = match Items::<PotentialArticle>::new(handle, "panya") {
         Ok(c) => c,
-        Err(err) => {
-            return handle_error(&err, "Items::new - can't open connection to db panya", &url)
-        }
+        Err(err) => return response.error(&err, "Items::new - can't open connection to db panya"),
     };
     let channels_coll = match Channels::new(handle, "panya") {
         Ok(c) => c,
         Err(err) => {
-            return handle_error(
-                &err,
-                "Channels::new - can't open connection to db panya",
-                &url,
-            )
+            return response.error(&err, "Channels::new - can't open connection to db panya")
         }
     };
     let items = return_db_articles(&name, limit, &items_coll).await;
-    // this is temporary
-    if items.is_empty() && channels_coll.find_one("name", url).await.is_none() {
-        if let Err(err) =
-            new_with_seq_db(&name, url, SourceType::Bakery, &channels_coll, &settings).await
-        {
-            eprintln!("{}", err);
-        } else {
-        }
+    let channel = match channels_coll.find_one("name", &name).await {
+        Some(c) => Some(c),
+        None => new_with_seq_db(&name, url, SourceType::Bakery, &channels_coll, settings)
+            .await
+            .map_err(|err| eprintln!("{}", err))
+            .ok(),
+    };
+    // channels that are not polled, such as bakery ones, are kept fresh by the calls
+    // to this endpoint, without the current response waiting for their source.
+    let channels: Vec<Channel> = channel.into_iter().collect();
+    if let Some(channel) = channels
+        .iter()
+        .find(|c| !registry().is_polled(c.source_type))
+    {
+        trigger_bakery(
+            Arc::clone(handle.inner()),
+            Arc::clone(scheduler.inner()),
+            settings.inner().clone(),
+            channel.clone(),
+        );
     }
-    RawXml(cook(url, &name, items))
+    response.cook(&name, &channels, items)
 }
@@ -1,28 +1,43 @@
 use std::sync::Arc;
 
-use rocket::http::{Cookie, CookieJar};
+use rocket::data::{Data, ToByteUnit};
+use rocket::http::{Accept, ContentType, Cookie, CookieJar};
+use rocket::response::content::RawXml;
 use rocket::serde::json::Json;
 use serde::{Deserialize, Serialize};
 
 use crate::config;
 use crate::db::channel::Channels;
+use crate::db::user::Users;
 use crate::entities::channel::Channel;
 use crate::error::Error;
 use crate::response::HTTPResponse;
+use crate::services::feed_token::{feed_token_url, hash_feed_token, new_feed_token};
 use crate::services::grpc::{user_login, user_signup};
+use crate::services::opml::{import_outlines, parse_opml, render_opml, OutlineReport};
+use crate::services::scheduler::FetchScheduler;
 use crate::services::token::extract_auth;
-use crate::{entities::user::User, error::HTTPError, request_guards::xqueryid::XQueryID};
+use crate::{
+    entities::user::User,
+    error::HTTPError,
+    request_guards::{publicurl::PublicUrl, xqueryid::XQueryID},
+};
 
 use crate::db::items::Items;
 use crate::db::model::{CollectionModel, SortOrder};
 use crate::entities::potential_articles::PotentialArticle;
-use crate::services::feed::GetFeedQuery;
+use crate::services::feed::{
+    find_user_page, public_uri, with_query_params, FeedBody, FeedFormat, FeedPage, GetFeedQuery,
+};
 use crate::utils::now_timestamp_ms;
 use crate::{config::Settings, db::mongo::Handle};
-use chrono::{Duration, Utc};
 use mongodb::bson::doc;
 
 use super::public_entities::public_channel::PublicChannel;
+
+// MiB
+const OPML_SIZE_LIMIT: u64 = 2;
+
 #[derive(Deserialize, Serialize, Debug)]
 pub struct UserPayload {
     pub login: String,
@@ -136,36 +151,203 @@ pub async fn show_user(
     Ok(Json(PublicChannel::from_channels(channels)))
 }
 
+// GET /panya/user/feed
+// A page of the items of the user's channels, as JSON, or as a feed when `format` or the
+// Accept header ask for one. Pages are read from the `before` or `after` cursors.
+// The JSON page is {items, next, previous}, and `limits` are refused with a 400:
+// /panya/feed still answers a bare array, limited per channel.
 #[get("/user/feed?<query..>")]
 pub async fn show_user_feed(
     db_handle: &rocket::State<Arc<Handle>>,
     settings: &rocket::State<Settings>,
     query: GetFeedQuery,
+    accept: Option<&Accept>,
+    public_url: PublicUrl<'_>,
     xquery_id: XQueryID,
     user: User,
-) -> Result<Json<Vec<PotentialArticle>>, HTTPError> {
+) -> Result<FeedBody, HTTPError> {
+    let bound = query.bound().map_err(HTTPError::BadRequest)?;
     let items_coll = Items::<PotentialArticle>::new(db_handle, "panya")?;
-    let max_limit = settings.default_item_per_feed;
     let time_bfore = now_timestamp_ms();
-    let mut items = items_coll
-        .find_with_limits(
-            "channel_id",
-            user.channel_ids,
-            query.limits.unwrap_or_default(),
-            max_limit,
-            ("create_date", SortOrder::DESC),
-            vec![(
-                "create_date",
-                doc! {"$gte": (Utc::now() - Duration::weeks(1)).timestamp_millis() },
-            )],
-        )
-        .await
-        .unwrap_or_else(|| vec![]);
+    let page = find_user_page(&items_coll, settings, &user, &query, bound).await?;
     info!(
         "({}): time for query: {}ms",
         xquery_id,
         now_timestamp_ms() - time_bfore
     );
-    items.sort_by(|a, b| b.cmp(a));
-    Ok(Json(items))
+    let format = match FeedFormat::negotiate(query.format.as_deref(), accept) {
+        Some(format) => format,
+        None => return Ok(FeedBody::Page(Json(page))),
+    };
+    Ok(FeedBody::Cooked(
+        cook_user_feed(db_handle, &public_url, &user, format, page).await?,
+    ))
+}
+
+/// cook_user_feed renders a page of a user's feed. JSON Feeds point at their next page,
+/// RSS items at the channels they come from.
+async fn cook_user_feed(
+    db_handle: &Handle,
+    public_url: &PublicUrl<'_>,
+    user: &User,
+    format: FeedFormat,
+    page: FeedPage,
+) -> Result<(ContentType, String), HTTPError> {
+    let channels = match format {
+        FeedFormat::Rss => Channels::<Channel>::new(db_handle, "panya")?
+            .find(doc! {"id": {"$in": &user.channel_ids}}, None, None, None)
+            .await
+            .unwrap_or_default(),
+        _ => vec![],
+    };
+    let next_url = page.next.map(|next| {
+        with_query_params(
+            &public_url.base,
+            public_url.origin,
+            &[("before", Some(&next)), ("after", None)],
+        )
+    });
+    Ok(format.cook(
+        &public_url.base,
+        &format!("{} on panya", user.username),
+        &public_uri(&public_url.base, public_url.origin),
+        next_url,
+        &channels,
+        page.items,
+    ))
+}
+
+#[derive(Serialize, Debug)]
+pub struct FeedTokenResponse {
+    pub token: String,
+    pub url: String,
+}
+
+// POST /panya/user/feed_token
+// Gives the user a new secret feed url, the previous one no longer working.
+// The token is only shown once, panya keeping its hash.
+#[post("/user/feed_token")]
+pub async fn rotate_user_feed_token(
+    db_handle: &rocket::State<Arc<Handle>>,
+    public_url: PublicUrl<'_>,
+    uuid: XQueryID,
+    user: User,
+) -> Result<Json<FeedTokenResponse>, HTTPError> {
+    let users_coll = Users::<User>::new(db_handle, "panya")?;
+    let token = new_feed_token();
+    users_coll
+        .set_feed_token(user.id, Some(&hash_feed_token(&token)))
+        .await?;
+    println!("[INFO] ({}) feed token rotated for user {}", uuid, user.id);
+    Ok(Json(FeedTokenResponse {
+        url: feed_token_url(&public_url.base, &token),
+        token,
+    }))
+}
+
+// DELETE /panya/user/feed_token
+#[delete("/user/feed_token")]
+pub async fn revoke_user_feed_token(
+    db_handle: &rocket::State<Arc<Handle>>,
+    uuid: XQueryID,
+    user: User,
+) -> Result<Json<HTTPResponse>, HTTPError> {
+    let users_coll = Users::<User>::new(db_handle, "panya")?;
+    users_coll.set_feed_token(user.id, None).await?;
+    println!("[INFO] ({}) feed token revoked for user {}", uuid, user.id);
+    Ok(Json(HTTPResponse::ok()))
+}
+
+// GET /panya/u/<token>/feed.xml
+// The user's feed, as RSS unless `format` or the Accept header ask otherwise,
+// for feed readers that cannot authenticate. The secret token stands for the user.
+#[get("/u/<token>/feed.xml?<query..>")]
+pub async fn show_user_public_feed(
+    db_handle: &rocket::State<Arc<Handle>>,
+    settings: &rocket::State<Settings>,
+    token: &str,
+    query: GetFeedQuery,
+    accept: Option<&Accept>,
+    public_url: PublicUrl<'_>,
+) -> Result<(ContentType, String), HTTPError> {
+    let users_coll = Users::<User>::new(db_handle, "panya")?;
+    let user = users_coll
+        .find_by_feed_token(&hash_feed_token(token))
+        .await
+        .ok_or_else(|| HTTPError::NotFound(Error::str("unknown feed")))?;
+    let bound = query.bound().map_err(HTTPError::BadRequest)?;
+    let items_coll = Items::<PotentialArticle>::new(db_handle, "panya")?;
+    let page = find_user_page(&items_coll, settings, &user, &query, bound).await?;
+    let format = FeedFormat::negotiate(query.format.as_deref(), accept).unwrap_or(FeedFormat::Rss);
+    cook_user_feed(db_handle, &public_url, &user, format, page).await
+}
+
+// POST /panya/user/opml
+// Subscribes the user to every outline of an OPML document, filing them in their folders.
+#[post("/user/opml", data = "<opml>")]
+pub async fn import_user_opml(
+    db_handle: &rocket::State<Arc<Handle>>,
+    scheduler: &rocket::State<Arc<FetchScheduler>>,
+    settings: &rocket::State<Settings>,
+    opml: Data<'_>,
+    uuid: XQueryID,
+    mut user: User,
+) -> Result<Json<Vec<OutlineReport>>, HTTPError> {
+    let raw = opml
+        .open(OPML_SIZE_LIMIT.mebibytes())
+        .into_string()
+        .await
+        .map_err(|err| HTTPError::BadRequest(Error(err.to_string())))?;
+    if !raw.is_complete() {
+        return Err(HTTPError::BadRequest(Error::str(
+            "opml document is too large",
+        )));
+    }
+    let outlines = parse_opml(&raw).map_err(HTTPError::BadRequest)?;
+    let channels_coll = Channels::<Channel>::new(db_handle, "panya")?;
+    let users_coll = Users::<User>::new(db_handle, "panya")?;
+    let reports = import_outlines(&channels_coll, scheduler, settings, outlines).await;
+
+    for report in &reports {
+        let channel_id = match report.channel_id {
+            Some(id) => id,
+            None => continue,
+        };
+        if !user.channel_ids.contains(&channel_id) {
+            user.channel_ids.push(channel_id);
+        }
+        if let Some(folder) = &report.folder {
+            user.file_channel(channel_id, folder);
+        }
+    }
+    if let Err(err) = users_coll.set_subscriptions(&user).await {
+        eprintln!("({}) {}", uuid, err);
+        return Err(HTTPError::InternalServerError(err));
+    }
+    Ok(Json(reports))
+}
+
+// GET /panya/user/opml
+#[get("/user/opml")]
+pub async fn export_user_opml(
+    db_handle: &rocket::State<Arc<Handle>>,
+    settings: &rocket::State<Settings>,
+    _uuid: XQueryID,
+    user: User,
+) -> Result<RawXml<String>, HTTPError> {
+    let channels_coll = Channels::<Channel>::new(db_handle, "panya")?;
+    let channels = channels_coll
+        .find(
+            doc! {"id": {"$in": &user.channel_ids}},
+            Some("name"),
+            SortOrder::ASC,
+            None,
+        )
+        .await
+        .unwrap_or_default();
+    Ok(RawXml(render_opml(
+        &format!("{} - {}", settings.app_name, user.username),
+        &PublicChannel::from_channels(channels),
+        &user.folders,
+    )?))
 }
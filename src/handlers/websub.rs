@@ -0,0 +1,161 @@
+use std::sync::Arc;
+
+use chrono::Utc;
+use rocket::data::{Data, ToByteUnit};
+use rocket::http::Status;
+
+use crate::config::Settings;
+use crate::db::{channel::Channels, model::CollectionModel, mongo::Handle};
+use crate::entities::channel::Channel;
+use crate::request_guards::xhubsignature::XHubSignature;
+use crate::request_guards::xqueryid::XQueryID;
+use crate::services::refresh::ingest_document;
+use crate::services::scheduler::FetchScheduler;
+use crate::services::websub::{subscription_secret, verify_signature, verify_subscription};
+
+// MiB
+const NOTIFICATION_SIZE_LIMIT: u64 = 5;
+
+/// HubQuery are the `hub.*` parameters of a hub's verification request
+#[derive(FromForm, Debug)]
+pub struct HubQuery {
+    mode: String,
+    topic: Option<String>,
+    challenge: Option<String>,
+    lease_seconds: Option<i64>,
+    reason: Option<String>,
+}
+
+// GET /panya/websub/<id>
+// Answers the verification of a (un)subscription by the hub, or its denial.
+#[get("/websub/<id>?<hub>")]
+pub async fn verify_websub(
+    db_handle: &rocket::State<Arc<Handle>>,
+    settings: &rocket::State<Settings>,
+    id: i32,
+    hub: HubQuery,
+    uuid: XQueryID,
+) -> Result<String, Status> {
+    if !settings.websub_enabled() {
+        return Err(Status::NotFound);
+    }
+    let channels_coll =
+        Channels::<Channel>::new(db_handle, "panya").map_err(|_| Status::InternalServerError)?;
+    let channel = channels_coll
+        .find_one("id", id)
+        .await
+        .ok_or(Status::NotFound)?;
+    // the channel's subscription, when it is the one the hub is talking about
+    let subscription = channel
+        .websub
+        .as_ref()
+        .filter(|subscription| hub.topic.as_ref() == Some(&subscription.topic));
+
+    match hub.mode.as_str() {
+        "subscribe" => {
+            let challenge = hub.challenge.ok_or(Status::BadRequest)?;
+            let subscription = subscription
+                .and_then(|subscription| {
+                    verify_subscription(
+                        settings,
+                        subscription,
+                        hub.lease_seconds,
+                        Utc::now().timestamp(),
+                    )
+                })
+                .ok_or(Status::NotFound)?;
+            channels_coll
+                .update_websub(id, Some(&subscription))
+                .await
+                .ok_or(Status::InternalServerError)?;
+            info!(
+                "({}) channel {} subscribed to {} for {:?}s",
+                uuid, id, subscription.hub, subscription.lease_seconds
+            );
+            Ok(challenge)
+        }
+        // only unsubscriptions from a topic the channel no longer follows are confirmed
+        "unsubscribe" => match subscription {
+            Some(_) => Err(Status::NotFound),
+            None => hub.challenge.ok_or(Status::BadRequest),
+        },
+        "denied" => {
+            if subscription.is_some() {
+                channels_coll
+                    .update_websub(id, None)
+                    .await
+                    .ok_or(Status::InternalServerError)?;
+            }
+            eprintln!(
+                "({}) channel {} subscription was denied: {}",
+                uuid,
+                id,
+                hub.reason.unwrap_or_default()
+            );
+            Ok(String::new())
+        }
+        _ => Err(Status::NotFound),
+    }
+}
+
+// POST /panya/websub/<id>
+// Ingests the content pushed by the hub. Notifications without a valid signature
+// are acknowledged, as the spec requires, but ignored.
+#[post("/websub/<id>", data = "<body>")]
+pub async fn receive_websub(
+    db_handle: &rocket::State<Arc<Handle>>,
+    scheduler: &rocket::State<Arc<FetchScheduler>>,
+    settings: &rocket::State<Settings>,
+    id: i32,
+    body: Data<'_>,
+    signature: XHubSignature,
+    uuid: XQueryID,
+) -> Status {
+    // without a secret of its own, anyone could sign notifications
+    if !settings.websub_enabled() {
+        return Status::NotFound;
+    }
+    let channels_coll = match Channels::<Channel>::new(db_handle, "panya") {
+        Ok(res) => res,
+        Err(_) => return Status::InternalServerError,
+    };
+    let channel = match channels_coll.find_one("id", id).await {
+        Some(res) => res,
+        None => return Status::NotFound,
+    };
+    let subscription = match &channel.websub {
+        Some(res) => res,
+        None => return Status::Gone,
+    };
+    let body = match body
+        .open(NOTIFICATION_SIZE_LIMIT.mebibytes())
+        .into_bytes()
+        .await
+    {
+        Ok(res) if res.is_complete() => res.into_inner(),
+        _ => return Status::PayloadTooLarge,
+    };
+    let secret = subscription_secret(settings, id, &subscription.hub, &subscription.topic);
+    let is_valid = signature
+        .0
+        .is_some_and(|signature| verify_signature(&secret, &signature, &body));
+    if !is_valid {
+        eprintln!(
+            "({}) channel {} received an unsigned or badly signed notification",
+            uuid, id
+        );
+        return Status::Accepted;
+    }
+
+    match ingest_document(db_handle, scheduler, &channel, &body).await {
+        Ok(stored) => info!(
+            "({}) channel {} received {} new items from its hub",
+            uuid, id, stored.inserted
+        ),
+        Err(err) => eprintln!(
+            "({}) channel {} could not ingest a notification: {}",
+            uuid, id, err
+        ),
+    }
+    Status::Accepted
+}
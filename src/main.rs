@@ -15,17 +15,29 @@ pub mod utils;
 pub mod workers;
 
 use handlers::{
-    channel::{add_url, delete_channel, get_channel, get_channel_list, update_channel},
+    channel::{
+        add_url, delete_channel, get_channel, get_channel_health, get_channel_list,
+        preview_json_api, preview_scraper, refresh_channel_now, update_channel,
+    },
     healthcheck::healthcheck,
     panya::get_url,
-    user::{add_user, login_user, show_user, show_user_channels, show_user_feed},
+    user::{
+        add_user, export_user_opml, import_user_opml, login_user, revoke_user_feed_token,
+        rotate_user_feed_token, show_user, show_user_channels, show_user_feed,
+        show_user_public_feed,
+    },
+    websub::{receive_websub, verify_websub},
 };
 use rocket::{
     fairing::{AdHoc, Fairing, Info, Kind},
     Build, Config, Data, Request, Response, Rocket, Route,
 };
 // use workers::identity::identity_new_user;
+use services::scheduler::FetchScheduler;
+use workers::refresh::refresh_worker;
 
+use db::{items::Items, user::Users};
+use entities::{potential_articles::PotentialArticle, user::User};
 use std::{net::Ipv4Addr, sync::Arc};
 use utils::now_timestamp_ms;
 use uuid::Uuid;
@@ -63,8 +75,29 @@ impl Fairing for XRequestIdMiddleware {
 
 async fn lezgong(routes: Vec<Route>, port: u16) -> Rocket<Build> {
     let settings = config::Settings::new().unwrap();
+    if let Some(reason) = settings.websub_disabled_reason() {
+        eprintln!("[ERR ] WebSub is disabled: {}", reason);
+    }
     let db_handle = Arc::new(db::mongo::get_handle(&settings).await);
+    match Items::<PotentialArticle>::new(&db_handle, "panya") {
+        Ok(items_coll) => {
+            if let Err(err) = items_coll.create_indexes().await {
+                eprintln!("[ERR ] Could not create items indexes: {}", err);
+            }
+        }
+        Err(err) => eprintln!("[ERR ] Could not create items indexes: {}", err),
+    }
+    match Users::<User>::new(&db_handle, "panya") {
+        Ok(users_coll) => {
+            if let Err(err) = users_coll.create_indexes().await {
+                eprintln!("[ERR ] Could not create users indexes: {}", err);
+            }
+        }
+        Err(err) => eprintln!("[ERR ] Could not create users indexes: {}", err),
+    }
     // let _ = identity_new_user(Arc::clone(&db_handle)).await;
+    let scheduler = Arc::new(FetchScheduler::from_settings(&settings));
+    let _ = refresh_worker(Arc::clone(&db_handle), Arc::clone(&scheduler), &settings).await;
     rocket::build()
         .configure(Config {
             port,
@@ -77,6 +110,7 @@ async fn lezgong(routes: Vec<Route>, port: u16) -> Rocket<Build> {
         })
         .mount("/panya", routes)
         .manage(db_handle)
+        .manage(scheduler)
         .manage(settings)
         .attach(XRequestIdMiddleware)
         .attach(AdHoc::on_request("time_before", |req, _| {
@@ -118,14 +152,25 @@ async fn launch() -> _ {
             // get_feed,
             get_channel_list,
             get_channel,
+            get_channel_health,
+            refresh_channel_now,
             update_channel,
+            preview_scraper,
+            preview_json_api,
             add_url,
             delete_channel,
             show_user,
             show_user_feed,
+            show_user_public_feed,
+            rotate_user_feed_token,
+            revoke_user_feed_token,
+            import_user_opml,
+            export_user_opml,
             show_user_channels,
             add_user,
             login_user,
+            verify_websub,
+            receive_websub,
         ],
         8083,
     )
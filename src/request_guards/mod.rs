@@ -1,3 +1,5 @@
 pub mod auth;
+pub mod publicurl;
 // pub mod user;
+pub mod xhubsignature;
 pub mod xqueryid;
@@ -0,0 +1,89 @@
+use rocket::http::uri::Origin;
+use rocket::http::Status;
+use rocket::request::{FromRequest, Outcome};
+use rocket::{Request, State};
+
+use crate::config::Settings;
+use crate::error::Error;
+
+/// PublicUrl is the absolute url panya is served under, without trailing slash,
+/// along with the uri of the request it was found for.
+#[derive(Debug)]
+pub struct PublicUrl<'r> {
+    pub base: String,
+    pub origin: &'r Origin<'r>,
+}
+
+/// public_base_url returns `public_url` when it is set, the url of the `host`
+/// the request was sent to otherwise.
+pub fn public_base_url(
+    public_url: &str,
+    host: Option<&str>,
+    forwarded_proto: Option<&str>,
+) -> Option<String> {
+    if !public_url.is_empty() {
+        return Some(public_url.trim_end_matches('/').to_string());
+    }
+    let host = host.filter(|host| !host.is_empty())?;
+    let scheme = match forwarded_proto {
+        Some("https") => "https",
+        _ => "http",
+    };
+    Some(format!("{}://{}", scheme, host))
+}
+
+// this request guard inject the public url of panya into handlers parameters,
+// so the urls handed to third-party readers are absolute even without `public_url`
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for PublicUrl<'r> {
+    type Error = Error;
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let settings = match req.guard::<&State<Settings>>().await {
+            Outcome::Success(settings) => settings,
+            _ => {
+                return Outcome::Error((
+                    Status::InternalServerError,
+                    Error::str("no settings found"),
+                ))
+            }
+        };
+        let host = req.host().map(|host| host.to_string());
+        match public_base_url(
+            &settings.public_url,
+            host.as_deref(),
+            req.headers().get_one("X-Forwarded-Proto"),
+        ) {
+            Some(base) => Outcome::Success(PublicUrl {
+                base,
+                origin: req.uri(),
+            }),
+            None => Outcome::Error((
+                Status::BadRequest,
+                Error::str("no public_url configured, nor Host header"),
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_i_can_get_public_base_url() {
+        assert_eq!(
+            public_base_url("https://panya.com/", Some("localhost:8000"), None).as_deref(),
+            Some("https://panya.com")
+        );
+        assert_eq!(
+            public_base_url("", Some("localhost:8000"), None).as_deref(),
+            Some("http://localhost:8000")
+        );
+        assert_eq!(
+            public_base_url("", Some("panya.com"), Some("https")).as_deref(),
+            Some("https://panya.com")
+        );
+        assert_eq!(public_base_url("", None, None), None);
+    }
+}
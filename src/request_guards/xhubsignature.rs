@@ -0,0 +1,20 @@
+use std::convert::Infallible;
+
+use rocket::request::{FromRequest, Outcome};
+use rocket::Request;
+
+#[derive(Debug)]
+pub struct XHubSignature(pub Option<String>);
+
+// this request guard inject the signature of a WebSub notification,
+// sent by the hub in the X-Hub-Signature header, into handlers parameters
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for XHubSignature {
+    type Error = Infallible;
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        Outcome::Success(XHubSignature(
+            req.headers().get_one("X-Hub-Signature").map(str::to_string),
+        ))
+    }
+}
@@ -1,17 +1,14 @@
-use rocket::error;
+use crate::services::{fetch::FetchError, scheduler::FetchScheduler};
 
-use crate::converters::string::to_articles;
-use crate::entities::potential_articles::PotentialArticle;
-
-/// get_cookies_from_bakery calls bakery, a website scrapper, and returns the result
-pub async fn get_cookies_from_bakery(api_path: &str, url: &str) -> Option<Vec<PotentialArticle>> {
-    let response = reqwest::get(format!("{}/bakery?url={}", api_path, url)).await;
-    let raw_data = match response {
-        Ok(res) => res.text().await.unwrap_or("[]".to_string()),
-        Err(err) => {
-            error!("{}", err);
-            return None;
-        }
-    };
-    Some(to_articles(&raw_data))
+/// request_bakery calls bakery, a website scrapper, and returns the articles it found
+/// in the page at `url`, as a JSON document.
+pub async fn request_bakery(
+    scheduler: &FetchScheduler,
+    api_path: &str,
+    url: &str,
+) -> Result<Vec<u8>, FetchError> {
+    let bakery_url = format!("{}/bakery?url={}", api_path, url);
+    let _permit = scheduler.acquire(&bakery_url).await;
+    let response = reqwest::get(bakery_url).await?.error_for_status()?;
+    Ok(response.bytes().await?.to_vec())
 }
@@ -1,4 +1,13 @@
-use crate::{entities::channel::SourceType, error::Error};
+use crate::{
+    config::Settings,
+    entities::channel::{ChannelHealth, SourceType},
+    error::Error,
+    services::{
+        discovery::{discover_feeds, pick_feed, FeedCandidate},
+        scheduler::FetchScheduler,
+        sources::registry,
+    },
+};
 
 pub fn compute_refresh_avg(current_avg: f32, time_to_refresh: i64, refresh_count: i32) -> f32 {
     // multiplying by 1000 to avoid to lose f32's 3 decimals precision
@@ -6,19 +15,206 @@ pub fn compute_refresh_avg(current_avg: f32, time_to_refresh: i64, refresh_count
         / ((refresh_count + 1) as i64 * 1000)) as f32
 }
 
-static RSS_CONTENT_TYPES: [&str; 2] = ["application/xml", "application/rss"];
+/// compute_refresh_frequency backs off the refresh frequency (ms) of a channel
+/// whose last fetch brought no new item, and brings it back toward its base frequency
+/// when new items show up. The result is bounded by `min` and `max`.
+pub fn compute_refresh_frequency(
+    current: i32,
+    base: i32,
+    new_items: usize,
+    min: i32,
+    max: i32,
+) -> i32 {
+    let next = if new_items == 0 {
+        current.saturating_add(current / 2)
+    } else {
+        (current / 2).max(base)
+    };
+    next.clamp(min, max.max(min))
+}
+
+/// compute_retry_delay returns the delay (s) before fetching again a channel
+/// that failed `consecutive_failures` times in a row, doubling `base` on each failure.
+pub fn compute_retry_delay(consecutive_failures: i32, base: i64, max: i64) -> i64 {
+    let exponent = (consecutive_failures - 1).clamp(0, 32) as u32;
+    base.saturating_mul(2_i64.saturating_pow(exponent)).min(max)
+}
+
+/// record_failure returns the health of a channel after a failed fetch at `now` (s).
+/// The channel is suspended once it reaches `failure_suspend_threshold` consecutive failures.
+pub fn record_failure(
+    health: &ChannelHealth,
+    error: &str,
+    http_status: Option<i32>,
+    now: i64,
+    settings: &Settings,
+) -> ChannelHealth {
+    let consecutive_failures = health.consecutive_failures.saturating_add(1);
+    ChannelHealth {
+        consecutive_failures,
+        last_error: Some(error.to_string()),
+        last_http_status: http_status,
+        next_retry: Some(
+            now + compute_retry_delay(
+                consecutive_failures,
+                settings.failure_backoff_base,
+                settings.failure_backoff_max,
+            ),
+        ),
+        suspended: health.suspended
+            || (settings.failure_suspend_threshold > 0
+                && consecutive_failures >= settings.failure_suspend_threshold),
+    }
+}
+
+/// source_type_from guesses the source type from a response's content type and document,
+/// asking each registered fetcher in turn.
+fn source_type_from(content_type: &str, body: &str) -> SourceType {
+    registry()
+        .detect(content_type, body)
+        .unwrap_or(SourceType::Bakery)
+}
 
-pub async fn find_out_source_type(channel_name: &str) -> Result<SourceType, Error> {
+pub async fn find_out_source_type(
+    scheduler: &FetchScheduler,
+    channel_name: &str,
+) -> Result<SourceType, Error> {
+    let _permit = scheduler.acquire(channel_name).await;
     let response = reqwest::get(channel_name).await?;
     let content_type = match response.headers().get("content-type") {
-        Some(header) => header.to_str().unwrap_or_default(),
-        None => "",
+        Some(header) => header.to_str().unwrap_or_default().to_string(),
+        None => "".to_string(),
+    };
+    let body = response.text().await.unwrap_or_default();
+    Ok(source_type_from(&content_type, &body))
+}
+
+pub enum SourceResolution {
+    Found {
+        url: String,
+        source_type: SourceType,
+    },
+    Ambiguous(Vec<FeedCandidate>),
+}
+
+/// resolve_source finds out the source type of `url`. HTML pages are searched
+/// for the feeds they advertise, scraping them through Bakery being the last resort.
+pub async fn resolve_source(
+    scheduler: &FetchScheduler,
+    url: &str,
+) -> Result<SourceResolution, Error> {
+    let _permit = scheduler.acquire(url).await;
+    let response = reqwest::get(url).await?;
+    let page_url = response.url().clone();
+    let content_type = match response.headers().get("content-type") {
+        Some(header) => header.to_str().unwrap_or_default().to_string(),
+        None => "".to_string(),
     };
-    if RSS_CONTENT_TYPES
-        .iter()
-        .any(|ct| content_type.find(ct).is_some())
-    {
-        return Ok(SourceType::RSSFeed);
+    let body = response.text().await.unwrap_or_default();
+    let source_type = source_type_from(&content_type, &body);
+    if source_type != SourceType::Bakery || !content_type.contains("html") {
+        return Ok(SourceResolution::Found {
+            url: url.to_string(),
+            source_type,
+        });
+    }
+
+    Ok(match pick_feed(discover_feeds(&body, &page_url)) {
+        Ok(Some(feed)) => SourceResolution::Found {
+            url: feed.url,
+            source_type: feed.source_type,
+        },
+        Ok(None) => SourceResolution::Found {
+            url: url.to_string(),
+            source_type: SourceType::Bakery,
+        },
+        Err(candidates) => SourceResolution::Ambiguous(candidates),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_i_can_compute_refresh_frequency() {
+        let (base, min, max) = (300_000, 300_000, 3_600_000);
+
+        assert_eq!(compute_refresh_frequency(base, base, 0, min, max), 450_000);
+        assert_eq!(compute_refresh_frequency(3_000_000, base, 0, min, max), max);
+        assert_eq!(
+            compute_refresh_frequency(1_000_000, base, 3, min, max),
+            500_000
+        );
+        assert_eq!(compute_refresh_frequency(400_000, base, 1, min, max), base);
+        assert_eq!(compute_refresh_frequency(base, 60_000, 1, min, max), min);
+    }
+
+    #[test]
+    fn test_i_can_compute_retry_delay() {
+        assert_eq!(compute_retry_delay(1, 60, 3600), 60);
+        assert_eq!(compute_retry_delay(2, 60, 3600), 120);
+        assert_eq!(compute_retry_delay(4, 60, 3600), 480);
+        assert_eq!(compute_retry_delay(7, 60, 3600), 3600);
+        assert_eq!(compute_retry_delay(i32::MAX, 60, 3600), 3600);
+    }
+
+    #[test]
+    fn test_i_can_record_failure() {
+        let mut settings = Settings::defaults();
+        settings.failure_backoff_base = 60;
+        settings.failure_backoff_max = 3600;
+        settings.failure_suspend_threshold = 3;
+
+        let health = record_failure(&ChannelHealth::default(), "timeout", None, 1000, &settings);
+        assert_eq!(health.consecutive_failures, 1);
+        assert_eq!(health.last_error, Some("timeout".to_string()));
+        assert_eq!(health.next_retry, Some(1060));
+        assert!(!health.suspended);
+        assert!(!health.is_ready(1059));
+        assert!(health.is_ready(1060));
+
+        let health = record_failure(&health, "not found", Some(404), 2000, &settings);
+        assert_eq!(health.last_http_status, Some(404));
+        assert_eq!(health.next_retry, Some(2120));
+        assert!(!health.suspended);
+
+        let health = record_failure(&health, "not found", Some(404), 3000, &settings);
+        assert!(health.suspended);
+        assert!(!health.is_ready(i64::MAX));
+    }
+
+    #[test]
+    fn test_i_can_find_out_source_type() {
+        let cases = [
+            ("application/rss+xml", "<rss></rss>", SourceType::RSSFeed),
+            (
+                "application/xml; charset=utf-8",
+                "<rss></rss>",
+                SourceType::RSSFeed,
+            ),
+            (
+                "application/atom+xml",
+                "<feed></feed>",
+                SourceType::AtomFeed,
+            ),
+            (
+                "application/xml",
+                "<feed xmlns=\"http://www.w3.org/2005/Atom\"></feed>",
+                SourceType::AtomFeed,
+            ),
+            ("application/feed+json", "{}", SourceType::JsonFeed),
+            (
+                "application/json",
+                "{\"version\": \"https://jsonfeed.org/version/1.1\"}",
+                SourceType::JsonFeed,
+            ),
+            ("application/json", "[]", SourceType::Bakery),
+            ("text/html", "<html></html>", SourceType::Bakery),
+        ];
+
+        for (content_type, body, source_type) in cases {
+            assert_eq!(source_type_from(content_type, body), source_type);
+        }
     }
-    Ok(SourceType::Bakery)
 }
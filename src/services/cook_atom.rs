@@ -0,0 +1,199 @@
+use atom_syndication::{
+    Category, Content, Entry, Feed, FixedDateTime, Generator, Link, Person, Text,
+};
+use chrono::Utc;
+use url::Url;
+
+use crate::entities::potential_articles::PotentialArticle;
+
+fn link(href: &str, rel: &str, mime_type: Option<&str>, length: Option<String>) -> Link {
+    Link {
+        href: href.to_string(),
+        rel: rel.to_string(),
+        mime_type: mime_type.map(str::to_string),
+        length,
+        ..Default::default()
+    }
+}
+
+impl From<&PotentialArticle> for Entry {
+    fn from(article: &PotentialArticle) -> Self {
+        let date: FixedDateTime = article.date_time().into();
+        // a link has to be an absolute iri
+        let mut links: Vec<Link> = Url::parse(&article.link)
+            .map(|_| link(&article.link, "alternate", Some("text/html"), None))
+            .into_iter()
+            .collect();
+        if let Some(enclosure) = &article.enclosure {
+            links.push(link(
+                &enclosure.url,
+                "enclosure",
+                Some(&enclosure.mime_type),
+                Some(enclosure.length.to_string()),
+            ));
+        }
+        Entry {
+            title: Text::plain(
+                article
+                    .title
+                    .clone()
+                    .unwrap_or_else(|| article.link.clone()),
+            ),
+            id: article.guid(),
+            updated: date,
+            published: Some(date),
+            links,
+            categories: article
+                .categories
+                .iter()
+                .flatten()
+                .map(|category| Category {
+                    term: category.clone(),
+                    ..Default::default()
+                })
+                .collect(),
+            summary: (!article.desc.is_empty()).then(|| Text::html(article.desc.clone())),
+            content: article.content_html.as_ref().map(|content_html| Content {
+                value: Some(content_html.clone()),
+                content_type: Some("html".to_string()),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+}
+
+/// cook_atom turns a vec of articles into an Atom feed. Its id is `link`, the url of the
+/// channel, and `self_url` is where the feed itself is served from. The id falls back
+/// to `self_url` when `link` is not an absolute url. The feed's author, standing for
+/// the authors of its entries, is named after its title.
+pub fn cook_atom(
+    link_url: &str,
+    title: &str,
+    self_url: &str,
+    articles: Vec<PotentialArticle>,
+) -> String {
+    let updated: FixedDateTime = articles
+        .iter()
+        .map(|article| article.date_time())
+        .max()
+        .unwrap_or_else(Utc::now)
+        .into();
+    let mut links = vec![link(self_url, "self", Some("application/atom+xml"), None)];
+    let id = match Url::parse(link_url) {
+        Ok(_) => {
+            links.push(link(link_url, "alternate", None, None));
+            link_url
+        }
+        Err(_) => self_url,
+    };
+    Feed {
+        title: Text::plain(title),
+        id: id.to_string(),
+        updated,
+        authors: vec![Person {
+            name: title.to_string(),
+            ..Default::default()
+        }],
+        links,
+        generator: Some(Generator {
+            value: "panya".to_string(),
+            ..Default::default()
+        }),
+        entries: articles.iter().map(Entry::from).collect(),
+        ..Default::default()
+    }
+    .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entities::potential_articles::Enclosure;
+
+    #[test]
+    fn test_i_can_cook_atom() {
+        let article = PotentialArticle {
+            link: "https://example.com/1".to_string(),
+            img: String::new(),
+            title: Some("First".to_string()),
+            desc: "<p>First article</p>".to_string(),
+            create_date: 1055217600000,
+            channel_name: Some("example.com".to_string()),
+            channel_id: Some(1),
+            categories: Some(vec!["news".to_string(), "tech".to_string()]),
+            key: "abc".to_string(),
+            content_html: None,
+            content_text: None,
+            enclosure: Some(Enclosure {
+                url: "https://example.com/1.mp3".to_string(),
+                mime_type: "audio/mpeg".to_string(),
+                length: 1024,
+                duration: None,
+            }),
+        };
+        let no_link = PotentialArticle {
+            link: String::new(),
+            title: None,
+            categories: None,
+            enclosure: None,
+            create_date: 1055304000000,
+            ..article.clone()
+        };
+        let cooked = cook_atom(
+            "https://example.com",
+            "example.com",
+            "https://panya.com/panya?url=https://example.com&format=atom",
+            vec![article, no_link],
+        );
+        let feed = Feed::read_from(cooked.as_bytes()).unwrap();
+
+        assert_eq!(feed.id(), "https://example.com");
+        assert_eq!(feed.title().as_str(), "example.com");
+        assert_eq!(feed.authors()[0].name(), "example.com");
+        assert_eq!(feed.updated().timestamp_millis(), 1055304000000);
+        assert_eq!(feed.links()[0].rel(), "self");
+        assert_eq!(
+            feed.links()[0].href(),
+            "https://panya.com/panya?url=https://example.com&format=atom"
+        );
+
+        let entry = &feed.entries()[0];
+        assert_eq!(entry.id(), "https://example.com/1");
+        assert_eq!(entry.title().as_str(), "First");
+        assert_eq!(entry.updated().timestamp_millis(), 1055217600000);
+        assert_eq!(entry.links()[0].href(), "https://example.com/1");
+        assert_eq!(entry.links()[1].rel(), "enclosure");
+        assert_eq!(entry.links()[1].length(), Some("1024"));
+        assert_eq!(
+            entry
+                .categories()
+                .iter()
+                .map(|category| category.term())
+                .collect::<Vec<&str>>(),
+            vec!["news", "tech"]
+        );
+        assert_eq!(entry.summary().unwrap().as_str(), "<p>First article</p>");
+        assert_eq!(feed.entries()[1].id(), "urn:panya:item:abc");
+        assert!(feed.entries()[1].links().is_empty());
+    }
+
+    #[test]
+    fn test_i_can_cook_atom_without_link() {
+        let cooked = cook_atom(
+            "",
+            "panya on panya",
+            "http://localhost:8000/panya/user/feed?format=atom",
+            vec![],
+        );
+        let feed = Feed::read_from(cooked.as_bytes()).unwrap();
+
+        assert_eq!(
+            feed.id(),
+            "http://localhost:8000/panya/user/feed?format=atom"
+        );
+        assert_eq!(feed.links().len(), 1);
+        assert_eq!(feed.links()[0].rel(), "self");
+        assert_eq!(feed.authors()[0].name(), "panya on panya");
+    }
+}
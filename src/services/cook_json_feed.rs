@@ -0,0 +1,167 @@
+use serde::Serialize;
+
+use crate::entities::potential_articles::PotentialArticle;
+
+const JSON_FEED_VERSION: &str = "https://jsonfeed.org/version/1.1";
+
+/// JSON Feed 1.1 document, as rendered by panya, see https://www.jsonfeed.org/version/1.1/
+#[derive(Debug, Serialize)]
+struct JsonFeedDocument {
+    version: &'static str,
+    title: String,
+    home_page_url: String,
+    feed_url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    next_url: Option<String>,
+    items: Vec<JsonFeedDocumentItem>,
+}
+
+#[derive(Debug, Serialize)]
+struct JsonFeedDocumentItem {
+    id: String,
+    url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    title: Option<String>,
+    content_html: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    summary: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    image: Option<String>,
+    date_published: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tags: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    attachments: Vec<JsonFeedAttachment>,
+}
+
+#[derive(Debug, Serialize)]
+struct JsonFeedAttachment {
+    url: String,
+    mime_type: String,
+    size_in_bytes: i64,
+}
+
+impl From<PotentialArticle> for JsonFeedDocumentItem {
+    fn from(article: PotentialArticle) -> Self {
+        let id = article.guid();
+        let date_published = article.date_time().to_rfc3339();
+        // the extracted content, when there is one, is the item's content,
+        // the description being its summary.
+        let (content_html, summary) = match article.content_html {
+            Some(content_html) => (content_html, Some(article.desc)),
+            None => (article.desc, None),
+        };
+        JsonFeedDocumentItem {
+            id,
+            url: article.link,
+            title: article.title,
+            content_html,
+            summary: summary.filter(|summary| !summary.is_empty()),
+            image: Some(article.img).filter(|img| !img.is_empty()),
+            date_published,
+            tags: article.categories.unwrap_or_default(),
+            attachments: article
+                .enclosure
+                .into_iter()
+                .map(|enclosure| JsonFeedAttachment {
+                    url: enclosure.url,
+                    mime_type: enclosure.mime_type,
+                    size_in_bytes: enclosure.length,
+                })
+                .collect(),
+        }
+    }
+}
+
+/// cook_json_feed turns a vec of articles into a JSON Feed. `link` is the url of the channel,
+/// `self_url` is where the feed itself is served from, and `next_url` where its next page is,
+/// read from the cursor of the last article so that none is skipped, see FeedPage.
+pub fn cook_json_feed(
+    link: &str,
+    title: &str,
+    self_url: &str,
+    next_url: Option<String>,
+    articles: Vec<PotentialArticle>,
+) -> String {
+    serde_json::to_string(&JsonFeedDocument {
+        version: JSON_FEED_VERSION,
+        title: title.to_string(),
+        home_page_url: link.to_string(),
+        feed_url: self_url.to_string(),
+        next_url,
+        items: articles
+            .into_iter()
+            .map(JsonFeedDocumentItem::from)
+            .collect(),
+    })
+    .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::converters::json_feed::JsonFeed;
+    use crate::db::model::PageCursor;
+    use crate::entities::potential_articles::Enclosure;
+    use serde_json::Value;
+
+    #[test]
+    fn test_i_can_cook_json_feed() {
+        let article = PotentialArticle {
+            link: "https://example.com/1".to_string(),
+            img: "https://example.com/1.png".to_string(),
+            title: Some("First".to_string()),
+            desc: "<p>First article</p>".to_string(),
+            create_date: 1055217600000,
+            channel_name: Some("example.com".to_string()),
+            channel_id: Some(1),
+            categories: Some(vec!["news".to_string()]),
+            key: "abc".to_string(),
+            content_html: None,
+            content_text: None,
+            enclosure: Some(Enclosure {
+                url: "https://example.com/1.mp3".to_string(),
+                mime_type: "audio/mpeg".to_string(),
+                length: 1024,
+                duration: None,
+            }),
+        };
+        let next_url = format!(
+            "https://panya.com/panya/user/feed?before={}",
+            PageCursor {
+                value: article.create_date,
+                id: mongodb::bson::oid::ObjectId::parse_str("65f1c2a4e13823a7c4b0f3d1").unwrap(),
+            }
+            .encode()
+        );
+        let cooked = cook_json_feed(
+            "https://example.com",
+            "example.com",
+            "https://panya.com/panya?url=https://example.com&format=json",
+            Some(next_url.clone()),
+            vec![article],
+        );
+        let feed: Value = serde_json::from_str(&cooked).unwrap();
+
+        assert_eq!(feed["version"], "https://jsonfeed.org/version/1.1");
+        assert_eq!(feed["home_page_url"], "https://example.com");
+        assert_eq!(feed["next_url"], next_url.as_str());
+        let item = &feed["items"][0];
+        assert_eq!(item["id"], "https://example.com/1");
+        assert_eq!(item["title"], "First");
+        assert_eq!(item["content_html"], "<p>First article</p>");
+        assert_eq!(item["image"], "https://example.com/1.png");
+        assert_eq!(item["date_published"], "2003-06-10T04:00:00+00:00");
+        assert_eq!(item["tags"][0], "news");
+        assert_eq!(item["attachments"][0]["size_in_bytes"], 1024);
+        assert!(item.get("summary").is_none());
+        // and panya can read it back
+        assert_eq!(
+            serde_json::from_str::<JsonFeed>(&cooked)
+                .unwrap()
+                .items
+                .len(),
+            1
+        );
+    }
+}
@@ -1,55 +1,304 @@
+use crate::entities::{channel::Channel, potential_articles::PotentialArticle};
+use chrono::Utc;
+use rss::{
+    extension::itunes::ITunesItemExtension, Category, ChannelBuilder, Enclosure, Guid, Item, Source,
+};
 use std::collections::BTreeMap;
-use rss::{ChannelBuilder, Item};
-use crate::entities::potential_articles::PotentialArticle;
+use url::Url;
+
+// ms in a minute, ttl being in minutes and refresh_frequency in ms
+const MS_PER_MINUTE: i32 = 60_000;
+
+/// image_mime_type guesses the mime type of an image from its url's extension
+fn image_mime_type(url: &str) -> &'static str {
+    let path = url
+        .split(['?', '#'])
+        .next()
+        .unwrap_or_default()
+        .to_lowercase();
+    match path.rsplit('.').next().unwrap_or_default() {
+        "png" => "image/png",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "svg" => "image/svg+xml",
+        "avif" => "image/avif",
+        _ => "image/jpeg",
+    }
+}
+
+/// rss_enclosure returns the article's enclosure, and its duration as an itunes extension.
+/// Articles without one have their image enclosed, its length being unknown.
+fn rss_enclosure(article: &PotentialArticle) -> (Option<Enclosure>, Option<ITunesItemExtension>) {
+    let enclosure = match &article.enclosure {
+        Some(res) => res,
+        None => {
+            return (
+                Url::parse(&article.img).ok().map(|_| Enclosure {
+                    url: article.img.clone(),
+                    // the RSS Best Practices Profile has unknown lengths be 0
+                    length: "0".to_string(),
+                    mime_type: image_mime_type(&article.img).to_string(),
+                }),
+                None,
+            )
+        }
+    };
+    (
+        Some(Enclosure {
+            url: enclosure.url.clone(),
+            length: enclosure.length.to_string(),
+            mime_type: enclosure.mime_type.clone(),
+        }),
+        enclosure
+            .duration
+            .as_ref()
+            .map(|duration| ITunesItemExtension {
+                duration: Some(duration.clone()),
+                ..Default::default()
+            }),
+    )
+}
+
+/// rss_guid is the article's link, as a permalink, when it is an absolute url
+fn rss_guid(article: &PotentialArticle) -> Guid {
+    Guid {
+        permalink: Url::parse(&article.link).is_ok(),
+        value: article.guid(),
+    }
+}
+
+/// rss_source points at the channel the article comes from, if it is known
+fn rss_source(article: &PotentialArticle, channels: &[Channel]) -> Option<Source> {
+    channels
+        .iter()
+        .find(|channel| Some(channel.id) == article.channel_id)
+        .filter(|channel| Url::parse(&channel.url).is_ok())
+        .map(|channel| Source {
+            url: channel.url.clone(),
+            title: Some(channel.name.clone()),
+        })
+}
 
 impl From<PotentialArticle> for Item {
     fn from(value: PotentialArticle) -> Self {
+        let (enclosure, itunes_ext) = rss_enclosure(&value);
         Item {
-            title: value.some_desc(),
-            link: value.some_link(),
+            // an item needs a title or a description, the html description making no title
+            title: value.title.clone().filter(|title| !title.is_empty()),
+            // a link has to be an absolute url
+            link: value.some_link().filter(|link| Url::parse(link).is_ok()),
             description: value.some_desc(),
             author: None,
-            categories: vec![],
+            categories: value
+                .categories
+                .iter()
+                .flatten()
+                .map(|category| Category {
+                    name: category.clone(),
+                    domain: None,
+                })
+                .collect(),
             comments: None,
-            enclosure: None,
-            guid: None,
-            pub_date: value.some_human_date(),
+            enclosure,
+            guid: Some(rss_guid(&value)),
+            pub_date: Some(value.date_time().to_rfc2822()),
             source: None,
-            content: None,
+            content: value.content_html,
             extensions: BTreeMap::new(),
-            itunes_ext: None,
+            itunes_ext,
             dublin_core_ext: None,
         }
     }
 }
 
-/// cook turns a vec of articles into xml using the std rss::ChannelBuilder
-pub fn cook(link: &str, title: &str, articles: Vec<PotentialArticle>) -> String {
-    let mut items = vec![];
-    for value in articles.iter() {
-        items.push(Item {
-            title: value.some_desc(),
-            link: Some(value.link.to_string()),
-            description: value.some_desc(),
-            author: None,
-            categories: vec![],
-            comments: None,
-            enclosure: None,
-            guid: None,
-            pub_date: value.some_human_date(),
-            source: None,
-            content: None,
-            extensions: BTreeMap::new(),
-            itunes_ext: None,
-            dublin_core_ext: None,
-        });
-    }
+/// cook turns a vec of articles into xml using the std rss::ChannelBuilder.
+/// `channels` are the channels the articles come from, their items pointing at them.
+/// The feed's ttl is the shortest of their refresh frequencies.
+/// Its link, having to be an absolute url, falls back to `self_url`.
+pub fn cook(
+    link: &str,
+    title: &str,
+    self_url: &str,
+    channels: &[Channel],
+    articles: Vec<PotentialArticle>,
+) -> String {
+    let last_build_date = articles
+        .iter()
+        .map(|article| article.date_time())
+        .max()
+        .unwrap_or_else(Utc::now);
+    let ttl = channels
+        .iter()
+        .map(|channel| (channel.refresh_frequency / MS_PER_MINUTE).max(1))
+        .min();
+    let items: Vec<Item> = articles
+        .into_iter()
+        .map(|article| {
+            let source = rss_source(&article, channels);
+            Item {
+                source,
+                ..Item::from(article)
+            }
+        })
+        .collect();
 
     ChannelBuilder::default()
         .title(title.to_string())
-        .link(link.to_string())
+        .link(match Url::parse(link) {
+            Ok(_) => link.to_string(),
+            Err(_) => self_url.to_string(),
+        })
         .description(title.to_string())
+        .last_build_date(Some(last_build_date.to_rfc2822()))
+        .ttl(ttl.map(|ttl| ttl.to_string()))
         .items(items)
         .build()
         .to_string()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entities::{channel::SourceType, potential_articles::Enclosure as ArticleEnclosure};
+    use rss::validation::Validate;
+
+    #[test]
+    fn test_i_can_cook_enclosures() {
+        let article = PotentialArticle {
+            link: "https://example.com/1".to_string(),
+            img: String::new(),
+            title: Some("Episode 1".to_string()),
+            desc: "First episode".to_string(),
+            create_date: 1055217600000,
+            channel_name: Some("example.com".to_string()),
+            channel_id: Some(1),
+            categories: None,
+            key: String::new(),
+            content_html: None,
+            content_text: None,
+            enclosure: Some(ArticleEnclosure {
+                url: "https://example.com/1.mp3".to_string(),
+                mime_type: "audio/mpeg".to_string(),
+                length: 24986239,
+                duration: Some("3723".to_string()),
+            }),
+        };
+        let cooked = cook(
+            "https://example.com",
+            "example.com",
+            "https://panya.com/panya/user/feed",
+            &[],
+            vec![article],
+        );
+        let channel = rss::Channel::read_from(cooked.as_bytes()).unwrap();
+        let item = &channel.items()[0];
+
+        let enclosure = item.enclosure().unwrap();
+        assert_eq!(enclosure.url(), "https://example.com/1.mp3");
+        assert_eq!(enclosure.length(), "24986239");
+        assert_eq!(enclosure.mime_type(), "audio/mpeg");
+        assert_eq!(item.itunes_ext().unwrap().duration(), Some("3723"));
+    }
+
+    #[test]
+    fn test_i_can_cook_valid_rss() {
+        let channel = Channel {
+            id: 1,
+            ..Channel::new(
+                "example.com/feed",
+                "https://example.com/feed",
+                SourceType::RSSFeed,
+                1_800_000,
+            )
+        };
+        let article = PotentialArticle {
+            link: "https://example.com/1".to_string(),
+            img: "https://example.com/1.png?w=300".to_string(),
+            title: Some("First".to_string()),
+            desc: "<p>First article</p>".to_string(),
+            create_date: 1055217600000,
+            channel_name: Some("example.com/feed".to_string()),
+            channel_id: Some(1),
+            categories: Some(vec!["news".to_string(), "tech".to_string()]),
+            key: "abc".to_string(),
+            content_html: None,
+            content_text: None,
+            enclosure: None,
+        };
+        let no_link = PotentialArticle {
+            link: "/relative".to_string(),
+            img: String::new(),
+            title: None,
+            categories: None,
+            create_date: 1055304000000,
+            ..article.clone()
+        };
+        let cooked = cook(
+            "https://example.com",
+            "example.com",
+            "https://panya.com/panya/?url=https://example.com/feed",
+            &[channel],
+            vec![no_link, article],
+        );
+        let rss = rss::Channel::read_from(cooked.as_bytes()).unwrap();
+        // the RSS Best Practices Profile has unknown enclosure lengths be 0,
+        // which the validator of the rss crate refuses.
+        let mut known_lengths = rss.clone();
+        for item in known_lengths.items_mut() {
+            if let Some(enclosure) = item.enclosure.as_mut().filter(|e| e.length == "0") {
+                enclosure.length = "1".to_string();
+            }
+        }
+        known_lengths.validate().unwrap();
+        assert_eq!(rss.link(), "https://example.com");
+
+        assert_eq!(
+            rss.last_build_date(),
+            Some("Wed, 11 Jun 2003 04:00:00 +0000")
+        );
+        assert_eq!(rss.ttl(), Some("30"));
+
+        let item = &rss.items()[1];
+        assert_eq!(item.title(), Some("First"));
+        assert_eq!(item.pub_date(), Some("Tue, 10 Jun 2003 04:00:00 +0000"));
+        assert_eq!(item.guid().unwrap().value(), "https://example.com/1");
+        assert!(item.guid().unwrap().is_permalink());
+        assert_eq!(
+            item.categories()
+                .iter()
+                .map(|category| category.name())
+                .collect::<Vec<&str>>(),
+            vec!["news", "tech"]
+        );
+        let source = item.source().unwrap();
+        assert_eq!(source.url(), "https://example.com/feed");
+        assert_eq!(source.title(), Some("example.com/feed"));
+        let enclosure = item.enclosure().unwrap();
+        assert_eq!(enclosure.url(), "https://example.com/1.png?w=300");
+        assert_eq!(enclosure.length(), "0");
+        assert_eq!(enclosure.mime_type(), "image/png");
+
+        let item = &rss.items()[0];
+        assert_eq!(item.title(), None);
+        assert_eq!(item.description(), Some("<p>First article</p>"));
+        assert_eq!(item.guid().unwrap().value(), "urn:panya:item:abc");
+        assert!(!item.guid().unwrap().is_permalink());
+        assert_eq!(item.link(), None);
+        assert!(item.enclosure().is_none());
+    }
+
+    #[test]
+    fn test_i_can_cook_a_valid_user_feed() {
+        // the user feed has no link of its own, as when `public_url` is not set
+        let cooked = cook(
+            "",
+            "panya on panya",
+            "http://localhost:8000/panya/u/abc/feed.xml",
+            &[],
+            vec![],
+        );
+        let rss = rss::Channel::read_from(cooked.as_bytes()).unwrap();
+        rss.validate().unwrap();
+        assert_eq!(rss.link(), "http://localhost:8000/panya/u/abc/feed.xml");
+    }
+}
@@ -0,0 +1,133 @@
+use scraper::{Html, Selector};
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use crate::entities::channel::SourceType;
+
+// advertised feed types, by order of preference
+static FEED_LINK_TYPES: [(&str, SourceType); 3] = [
+    ("application/rss+xml", SourceType::RSSFeed),
+    ("application/atom+xml", SourceType::AtomFeed),
+    ("application/feed+json", SourceType::JsonFeed),
+];
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct FeedCandidate {
+    pub url: String,
+    pub title: Option<String>,
+    pub source_type: SourceType,
+}
+
+/// discover_feeds lists the feeds advertised by an HTML page through
+/// `<link rel="alternate">` tags, resolving their url against `base`.
+pub fn discover_feeds(html: &str, base: &Url) -> Vec<FeedCandidate> {
+    let selector = match Selector::parse(r#"link[rel~="alternate"][type][href]"#) {
+        Ok(res) => res,
+        Err(_) => return vec![],
+    };
+    let mut candidates: Vec<FeedCandidate> = vec![];
+    for link in Html::parse_document(html).select(&selector) {
+        let link_type = link.attr("type").unwrap_or_default().trim().to_lowercase();
+        let source_type = match FEED_LINK_TYPES.iter().find(|(ft, _)| *ft == link_type) {
+            Some((_, source_type)) => *source_type,
+            None => continue,
+        };
+        let url = match link
+            .attr("href")
+            .and_then(|href| base.join(href.trim()).ok())
+        {
+            Some(res) => res.to_string(),
+            None => continue,
+        };
+        if candidates.iter().any(|candidate| candidate.url == url) {
+            continue;
+        }
+        candidates.push(FeedCandidate {
+            url,
+            title: link.attr("title").map(str::to_string),
+            source_type,
+        });
+    }
+    candidates
+}
+
+fn is_comments_feed(candidate: &FeedCandidate) -> bool {
+    candidate.url.to_lowercase().contains("comments")
+        || candidate
+            .title
+            .as_ref()
+            .is_some_and(|title| title.to_lowercase().contains("comments"))
+}
+
+/// pick_feed chooses the feed to subscribe to among the candidates of a page.
+/// Comments feeds are ignored, and a single feed of the preferred type wins.
+/// `Err` holds the remaining candidates when several feeds of that type are advertised.
+pub fn pick_feed(
+    candidates: Vec<FeedCandidate>,
+) -> Result<Option<FeedCandidate>, Vec<FeedCandidate>> {
+    let candidates: Vec<FeedCandidate> = candidates
+        .into_iter()
+        .filter(|candidate| !is_comments_feed(candidate))
+        .collect();
+    let preferred = match FEED_LINK_TYPES
+        .iter()
+        .find(|(_, st)| candidates.iter().any(|c| c.source_type == *st))
+    {
+        Some((_, source_type)) => *source_type,
+        None => return Ok(None),
+    };
+    let mut preferred_candidates: Vec<FeedCandidate> = candidates
+        .iter()
+        .filter(|candidate| candidate.source_type == preferred)
+        .cloned()
+        .collect();
+    if preferred_candidates.len() > 1 {
+        return Err(candidates);
+    }
+    Ok(preferred_candidates.pop())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PAGE: &str = r#"<html><head>
+        <link rel="stylesheet" href="/style.css">
+        <link rel="alternate" type="application/rss+xml" title="Blog" href="/feed/">
+        <link rel="alternate" type="application/rss+xml" title="Blog &raquo; Comments Feed" href="/comments/feed/">
+        <link rel="alternate" type="application/atom+xml" title="Blog (Atom)" href="https://example.com/atom.xml">
+        <link rel="alternate" type="application/json" href="/wp-json/">
+        <link rel="alternate" hreflang="fr" href="/fr/">
+    </head><body></body></html>"#;
+
+    #[test]
+    fn test_i_can_discover_feeds() {
+        let base = Url::parse("https://example.com/blog/").unwrap();
+        let candidates = discover_feeds(PAGE, &base);
+
+        assert_eq!(candidates.len(), 3);
+        assert_eq!(candidates[0].url, "https://example.com/feed/");
+        assert_eq!(candidates[0].source_type, SourceType::RSSFeed);
+        assert_eq!(candidates[0].title, Some("Blog".to_string()));
+        assert_eq!(candidates[1].url, "https://example.com/comments/feed/");
+        assert_eq!(candidates[2].source_type, SourceType::AtomFeed);
+    }
+
+    #[test]
+    fn test_i_can_pick_feed() {
+        let base = Url::parse("https://example.com/").unwrap();
+        let picked = pick_feed(discover_feeds(PAGE, &base)).unwrap().unwrap();
+        assert_eq!(picked.url, "https://example.com/feed/");
+
+        assert_eq!(pick_feed(vec![]), Ok(None));
+
+        let ambiguous = r#"<link rel="alternate" type="application/atom+xml" href="/news.xml">
+            <link rel="alternate" type="application/atom+xml" href="/videos.xml">"#;
+        assert_eq!(
+            pick_feed(discover_feeds(ambiguous, &base))
+                .unwrap_err()
+                .len(),
+            2
+        );
+    }
+}
@@ -0,0 +1,321 @@
+use std::collections::HashMap;
+
+use scraper::{ElementRef, Html, Node, Selector};
+use url::Url;
+
+use crate::{
+    error::Error,
+    services::{link_op::resolve_link, scheduler::FetchScheduler},
+};
+
+// tags that never hold an article's content, dropped along with their children
+static SKIPPED_TAGS: [&str; 14] = [
+    "script", "style", "noscript", "iframe", "form", "nav", "aside", "footer", "header", "button",
+    "svg", "input", "select", "textarea",
+];
+// tags kept in the extracted html, the others being replaced by their content
+static KEPT_TAGS: [&str; 27] = [
+    "p",
+    "br",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "ul",
+    "ol",
+    "li",
+    "blockquote",
+    "pre",
+    "code",
+    "em",
+    "strong",
+    "b",
+    "i",
+    "a",
+    "img",
+    "figure",
+    "figcaption",
+    "table",
+    "thead",
+    "tbody",
+    "tr",
+    "td",
+];
+// tags separating blocks of text
+static BLOCK_TAGS: [&str; 17] = [
+    "p",
+    "br",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "li",
+    "blockquote",
+    "pre",
+    "figure",
+    "figcaption",
+    "tr",
+    "div",
+    "section",
+    "article",
+];
+static POSITIVE_HINTS: [&str; 7] = [
+    "article", "content", "entry", "main", "post", "story", "text",
+];
+static NEGATIVE_HINTS: [&str; 11] = [
+    "comment", "footer", "sidebar", "nav", "menu", "share", "social", "related", "promo", "banner",
+    "cookie",
+];
+// extracted content shorter than this (chars) is most likely not the article
+const MIN_TEXT_LENGTH: usize = 140;
+
+#[derive(Debug, PartialEq)]
+pub struct ExtractedContent {
+    pub html: String,
+    pub text: String,
+}
+
+/// class_weight favors elements whose class or id hint at an article,
+/// and penalizes the ones hinting at comments, menus and the like.
+fn class_weight(element: &ElementRef) -> f32 {
+    let hints = format!(
+        "{} {}",
+        element.value().attr("class").unwrap_or_default(),
+        element.value().id().unwrap_or_default()
+    )
+    .to_lowercase();
+    let mut weight = 0.;
+    if POSITIVE_HINTS.iter().any(|hint| hints.contains(hint)) {
+        weight += 25.;
+    }
+    if NEGATIVE_HINTS.iter().any(|hint| hints.contains(hint)) {
+        weight -= 25.;
+    }
+    weight
+}
+
+fn text_length(element: &ElementRef) -> usize {
+    element.text().map(|text| text.trim().len()).sum()
+}
+
+/// link_density is the share of an element's text found in links
+fn link_density(element: &ElementRef, links: &Selector) -> f32 {
+    let length = text_length(element);
+    if length == 0 {
+        return 0.;
+    }
+    let links_length: usize = element.select(links).map(|a| text_length(&a)).sum();
+    links_length as f32 / length as f32
+}
+
+/// find_main_content scores the parents of every paragraph, readability style:
+/// a paragraph is worth more the longer it is and the more commas it has,
+/// its parent getting its full score and its grandparent half of it.
+fn find_main_content(document: &Html) -> Option<ElementRef<'_>> {
+    let paragraphs = Selector::parse("p, pre, td").ok()?;
+    let links = Selector::parse("a").ok()?;
+    let mut scores = HashMap::new();
+
+    for paragraph in document.select(&paragraphs) {
+        let text = paragraph.text().collect::<String>();
+        let length = text.trim().len();
+        if length < 25 {
+            continue;
+        }
+        let score = 1. + text.matches(',').count() as f32 + (length as f32 / 100.).min(3.);
+        let mut ancestors = paragraph.ancestors().filter_map(ElementRef::wrap);
+        for share in [1., 0.5] {
+            let ancestor = match ancestors.next() {
+                Some(res) => res,
+                None => break,
+            };
+            *scores
+                .entry(ancestor.id())
+                .or_insert_with(|| class_weight(&ancestor)) += score * share;
+        }
+    }
+
+    scores
+        .into_iter()
+        .filter_map(|(id, score)| {
+            let element = ElementRef::wrap(document.tree.get(id)?)?;
+            Some((element, score * (1. - link_density(&element, &links))))
+        })
+        .max_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(element, _)| element)
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// render writes a cleaned copy of `element` in `html`, and its text in `text`.
+fn render(element: ElementRef, base: &Url, html: &mut String, text: &mut String) {
+    for child in element.children() {
+        match child.value() {
+            Node::Text(content) => {
+                html.push_str(&escape_html(content));
+                text.push_str(content);
+            }
+            Node::Element(_) => {
+                let child = match ElementRef::wrap(child) {
+                    Some(res) => res,
+                    None => continue,
+                };
+                let tag = child.value().name();
+                if SKIPPED_TAGS.contains(&tag) {
+                    continue;
+                }
+                let is_block = BLOCK_TAGS.contains(&tag);
+                if is_block {
+                    text.push('\n');
+                }
+                if !KEPT_TAGS.contains(&tag) {
+                    render(child, base, html, text);
+                    continue;
+                }
+                match tag {
+                    "br" => html.push_str("<br>"),
+                    "img" => {
+                        let src = child
+                            .value()
+                            .attr("src")
+                            .or(child.value().attr("data-src"))
+                            .and_then(|src| resolve_link(base, src));
+                        if let Some(src) = src {
+                            html.push_str(&format!(
+                                "<img src=\"{}\" alt=\"{}\">",
+                                escape_html(&src),
+                                escape_html(child.value().attr("alt").unwrap_or_default())
+                            ));
+                        }
+                    }
+                    "a" => {
+                        match child
+                            .value()
+                            .attr("href")
+                            .and_then(|href| resolve_link(base, href))
+                        {
+                            Some(href) => {
+                                html.push_str(&format!("<a href=\"{}\">", escape_html(&href)))
+                            }
+                            None => html.push_str("<a>"),
+                        }
+                        render(child, base, html, text);
+                        html.push_str("</a>");
+                    }
+                    _ => {
+                        html.push_str(&format!("<{}>", tag));
+                        render(child, base, html, text);
+                        html.push_str(&format!("</{}>", tag));
+                    }
+                }
+                if is_block {
+                    text.push('\n');
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// extract_content finds the main content of an article's page and returns it
+/// as cleaned html, stripped of scripts, menus and other page chrome, and as plain text.
+/// Links and images are resolved against `base`, the page's url.
+pub fn extract_content(page: &str, base: &Url) -> Option<ExtractedContent> {
+    let document = Html::parse_document(page);
+    let main_content = find_main_content(&document)?;
+    let mut html = String::new();
+    let mut text = String::new();
+    render(main_content, base, &mut html, &mut text);
+
+    let text = text
+        .lines()
+        .map(|line| line.split_whitespace().collect::<Vec<&str>>().join(" "))
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<String>>()
+        .join("\n\n");
+    if text.len() < MIN_TEXT_LENGTH {
+        return None;
+    }
+    Some(ExtractedContent {
+        html: html.trim().to_string(),
+        text,
+    })
+}
+
+/// fetch_content downloads an article's page and extracts its main content
+pub async fn fetch_content(
+    scheduler: &FetchScheduler,
+    link: &str,
+) -> Result<Option<ExtractedContent>, Error> {
+    let _permit = scheduler.acquire(link).await;
+    let response = reqwest::get(link).await?.error_for_status()?;
+    let base = response.url().clone();
+    let page = response.text().await?;
+    Ok(extract_content(&page, &base))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PAGE: &str = r#"<html><head><title>Page</title><script>var a = 1;</script></head>
+<body>
+  <header><nav><a href="/">Home</a> <a href="/news">News</a></nav></header>
+  <div class="layout">
+    <div id="sidebar" class="sidebar"><p>Subscribe to our newsletter, it is free, weekly, and full of news.</p></div>
+    <article class="post-content">
+      <h1>The title</h1>
+      <p>First paragraph of the article, long enough to be considered content, with a few commas, here and there.</p>
+      <script>track();</script>
+      <p>Second paragraph, with <a href="/related" onclick="x()">a link</a> and an image:
+        <img src="/img/photo.jpg" alt="A &quot;photo&quot;"></p>
+      <div class="share">Share this: <a href="https://twitter.com">Twitter</a></div>
+      <p>Third paragraph, 1 < 2 &amp; the article, finally, ends here with some more words.</p>
+    </article>
+    <div class="comments"><p>A comment that is long enough to be a paragraph, but, well, it is a comment.</p></div>
+  </div>
+  <footer><p>Copyright, all rights reserved, nothing to see here, move along please.</p></footer>
+</body></html>"#;
+
+    #[test]
+    fn test_i_can_extract_content() {
+        let base = Url::parse("https://example.com/2024/article").unwrap();
+        let content = extract_content(PAGE, &base).unwrap();
+
+        assert!(content.html.starts_with("<h1>The title</h1>"));
+        assert!(content
+            .html
+            .contains(r#"<a href="https://example.com/related">a link</a>"#));
+        assert!(content.html.contains(
+            r#"<img src="https://example.com/img/photo.jpg" alt="A &quot;photo&quot;">"#
+        ));
+        assert!(content.html.contains("1 &lt; 2 &amp; the article"));
+        assert!(!content.html.contains("track()"));
+        assert!(!content.html.contains("<div"));
+        assert!(!content.html.contains("newsletter"));
+        assert!(!content.html.contains("comment"));
+        assert!(content
+            .text
+            .starts_with("The title\n\nFirst paragraph of the article"));
+        assert!(content.text.ends_with("ends here with some more words."));
+    }
+
+    #[test]
+    fn test_i_cannot_extract_content_from_an_empty_page() {
+        let base = Url::parse("https://example.com/").unwrap();
+
+        assert_eq!(
+            extract_content("<html><body><p>Too short.</p></body></html>", &base),
+            None
+        );
+    }
+}
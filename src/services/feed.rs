@@ -1,8 +1,315 @@
 use std::collections::HashMap;
 
 use mongodb::bson::doc;
+use rocket::http::uri::Origin;
+use rocket::http::{Accept, ContentType, MediaType};
+use rocket::serde::json::Json;
+use serde::Serialize;
+
+use crate::config::Settings;
+use crate::db::items::Items;
+use crate::db::model::{CollectionModel, Page, PageBound, PageCursor};
+use crate::entities::{channel::Channel, potential_articles::PotentialArticle, user::User};
+use crate::error::Error;
+use crate::services::{cook_atom::cook_atom, cook_json_feed::cook_json_feed, cook_rss::cook};
 
 #[derive(FromForm)]
 pub struct GetFeedQuery {
+    // items per channel, get_feed only
     pub limits: Option<HashMap<i32, i64>>,
+    // includes the extracted content of the items
+    pub content: Option<bool>,
+    // cursors handed out by a previous page, the page being read from the items
+    // older than `before`, or newer than `after`
+    pub before: Option<String>,
+    pub after: Option<String>,
+    // items per page, up to `feed_max_page_size`
+    pub page_size: Option<i64>,
+    // rss, atom or json, the Accept header deciding otherwise
+    pub format: Option<String>,
+}
+
+impl GetFeedQuery {
+    /// bound returns the cursor the page is read from, if any.
+    /// Pages have no per-channel limits, these being refused rather than ignored.
+    pub fn bound(&self) -> Result<Option<PageBound>, Error> {
+        if self.limits.is_some() {
+            return Error::str_to_result(
+                "limits are not supported by paged feeds, use /panya/feed or page_size",
+            );
+        }
+        match (self.before.as_deref(), self.after.as_deref()) {
+            (Some(_), Some(_)) => Error::str_to_result("before and after cannot be used together"),
+            (Some(before), None) => Ok(Some(PageBound::Before(PageCursor::decode(before)?))),
+            (None, Some(after)) => Ok(Some(PageBound::After(PageCursor::decode(after)?))),
+            (None, None) => Ok(None),
+        }
+    }
+
+    pub fn page_size(&self, settings: &Settings) -> i64 {
+        self.page_size
+            .unwrap_or(settings.feed_page_size)
+            .min(settings.feed_max_page_size)
+            .max(1)
+    }
+}
+
+/// FeedPage is a page of the user feed, the newest items first. `next` is the cursor
+/// of the older items, if any, `previous` the one of the newer items to come.
+#[derive(Debug, Serialize)]
+pub struct FeedPage {
+    pub items: Vec<PotentialArticle>,
+    pub next: Option<String>,
+    pub previous: Option<String>,
+}
+
+impl FeedPage {
+    fn new(page: Page<PotentialArticle>, bound: Option<PageBound>) -> Self {
+        let (older, newer) = match bound {
+            None => (page.has_more, None),
+            // the cursor's item, at least, is older than a page read after it
+            Some(PageBound::After(cursor)) => (true, Some(cursor)),
+            Some(PageBound::Before(cursor)) => (page.has_more, Some(cursor)),
+        };
+        FeedPage {
+            next: page.last.filter(|_| older).map(|cursor| cursor.encode()),
+            previous: page.first.or(newer).map(|cursor| cursor.encode()),
+            items: page.items,
+        }
+    }
+}
+
+/// find_user_page returns a page of the items of the user's channels, the newest first.
+/// Pages are read from the query's cursor, if any.
+pub async fn find_user_page(
+    items_coll: &Items<'_, PotentialArticle>,
+    settings: &Settings,
+    user: &User,
+    query: &GetFeedQuery,
+    bound: Option<PageBound>,
+) -> Result<FeedPage, Error> {
+    let page = items_coll
+        .find_page(
+            doc! {"channel_id": {"$in": &user.channel_ids}},
+            "create_date",
+            bound.clone(),
+            query.page_size(settings),
+        )
+        .await?;
+    let mut page = FeedPage::new(page, bound);
+    if !query.content.unwrap_or_default() {
+        for item in page.items.iter_mut() {
+            item.content_html = None;
+            item.content_text = None;
+        }
+    }
+    Ok(page)
+}
+
+/// FeedFormat is a format feeds can be rendered in
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FeedFormat {
+    Rss,
+    Atom,
+    Json,
+}
+
+impl FeedFormat {
+    fn from_name(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "rss" => Some(FeedFormat::Rss),
+            "atom" => Some(FeedFormat::Atom),
+            "json" => Some(FeedFormat::Json),
+            _ => None,
+        }
+    }
+
+    fn from_media_type(media_type: &MediaType) -> Option<Self> {
+        if media_type.top() != "application" {
+            return None;
+        }
+        match media_type.sub().as_str().to_lowercase().as_str() {
+            "rss+xml" => Some(FeedFormat::Rss),
+            "atom+xml" => Some(FeedFormat::Atom),
+            "feed+json" => Some(FeedFormat::Json),
+            _ => None,
+        }
+    }
+
+    /// negotiate picks the format asked by the `format` query parameter, else the
+    /// preferred one of the Accept header, if any is a feed format.
+    pub fn negotiate(format: Option<&str>, accept: Option<&Accept>) -> Option<Self> {
+        if let Some(format) = format.and_then(FeedFormat::from_name) {
+            return Some(format);
+        }
+        let mut media_types: Vec<_> = accept
+            .map(|accept| accept.iter().collect())
+            .unwrap_or_default();
+        // sort_by is stable, media types of equal weight keep their order
+        media_types.sort_by(|a, b| b.weight_or(1.0).total_cmp(&a.weight_or(1.0)));
+        media_types
+            .iter()
+            .find_map(|media_type| FeedFormat::from_media_type(media_type.media_type()))
+    }
+
+    pub fn content_type(&self) -> ContentType {
+        match self {
+            FeedFormat::Rss => ContentType::XML,
+            FeedFormat::Atom => ContentType::new("application", "atom+xml"),
+            FeedFormat::Json => ContentType::new("application", "feed+json"),
+        }
+    }
+
+    /// cook renders articles as a feed of this format. `link` is the url of the channel,
+    /// `self_url` where the feed is served from, and `next_url` where its next page is.
+    /// Only JSON Feed has a mean of pointing at the next page, and only RSS at the `channels`
+    /// the articles come from.
+    pub fn cook(
+        &self,
+        link: &str,
+        title: &str,
+        self_url: &str,
+        next_url: Option<String>,
+        channels: &[Channel],
+        articles: Vec<PotentialArticle>,
+    ) -> (ContentType, String) {
+        let feed = match self {
+            FeedFormat::Rss => cook(link, title, self_url, channels, articles),
+            FeedFormat::Atom => cook_atom(link, title, self_url, articles),
+            FeedFormat::Json => cook_json_feed(link, title, self_url, next_url, articles),
+        };
+        (self.content_type(), feed)
+    }
+}
+
+/// FeedBody is the body of the user feed: a page of its articles as JSON,
+/// unless a feed format was negotiated.
+#[derive(Responder)]
+pub enum FeedBody {
+    Page(Json<FeedPage>),
+    Cooked((ContentType, String)),
+}
+
+/// public_uri returns the absolute uri of `origin`, as served under `public_url`
+pub fn public_uri(public_url: &str, origin: &Origin<'_>) -> String {
+    format!("{}{}", public_url.trim_end_matches('/'), origin)
+}
+
+/// with_query_params returns the absolute uri of `origin`, its query parameters
+/// named in `params` being set to their value, or removed when it is `None`.
+pub fn with_query_params(
+    public_url: &str,
+    origin: &Origin<'_>,
+    params: &[(&str, Option<&str>)],
+) -> String {
+    let mut query: Vec<String> = origin
+        .query()
+        .map(|query| {
+            query
+                .as_str()
+                .split('&')
+                .filter(|param| {
+                    let name = param.split('=').next().unwrap_or_default();
+                    !param.is_empty() && !params.iter().any(|(n, _)| *n == name)
+                })
+                .map(str::to_string)
+                .collect()
+        })
+        .unwrap_or_default();
+    for (name, value) in params {
+        if let Some(value) = value {
+            query.push(format!("{}={}", name, value));
+        }
+    }
+    let uri = format!("{}{}", public_url.trim_end_matches('/'), origin.path());
+    match query.is_empty() {
+        true => uri,
+        false => format!("{}?{}", uri, query.join("&")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_i_can_negotiate_a_feed_format() {
+        let accept =
+            Accept::from_str("text/html, application/atom+xml;q=0.9, application/rss+xml;q=0.5")
+                .unwrap();
+        assert_eq!(
+            FeedFormat::negotiate(None, Some(&accept)),
+            Some(FeedFormat::Atom)
+        );
+        assert_eq!(
+            FeedFormat::negotiate(Some("rss"), Some(&accept)),
+            Some(FeedFormat::Rss)
+        );
+        assert_eq!(
+            FeedFormat::negotiate(Some("ATOM"), None),
+            Some(FeedFormat::Atom)
+        );
+        assert_eq!(FeedFormat::negotiate(Some("unknown"), None), None);
+
+        let accept = Accept::from_str("application/atom+xml;q=0.2, application/feed+json").unwrap();
+        assert_eq!(
+            FeedFormat::negotiate(None, Some(&accept)),
+            Some(FeedFormat::Json)
+        );
+        let accept = Accept::from_str("application/json").unwrap();
+        assert_eq!(FeedFormat::negotiate(None, Some(&accept)), None);
+    }
+
+    #[test]
+    fn test_i_can_set_query_params() {
+        let origin = Origin::parse("/panya/user/feed?format=json&after=12&page_size=5").unwrap();
+        assert_eq!(
+            with_query_params(
+                "https://panya.com/",
+                &origin,
+                &[("before", Some("10")), ("after", None)]
+            ),
+            "https://panya.com/panya/user/feed?format=json&page_size=5&before=10"
+        );
+        let origin = Origin::parse("/panya/user/feed?after=12").unwrap();
+        assert_eq!(
+            with_query_params("", &origin, &[("after", None)]),
+            "/panya/user/feed"
+        );
+    }
+
+    #[test]
+    fn test_i_can_point_at_the_pages_around() {
+        let cursor = |value| PageCursor {
+            value,
+            id: mongodb::bson::oid::ObjectId::parse_str("65f1c2a4e13823a7c4b0f3d1").unwrap(),
+        };
+        let page = |has_more| Page::<PotentialArticle> {
+            items: vec![],
+            first: Some(cursor(3)),
+            last: Some(cursor(2)),
+            has_more,
+        };
+
+        let first_page = FeedPage::new(page(true), None);
+        assert_eq!(first_page.next, Some(cursor(2).encode()));
+        assert_eq!(first_page.previous, Some(cursor(3).encode()));
+
+        let last_page = FeedPage::new(page(false), Some(PageBound::Before(cursor(4))));
+        assert_eq!(last_page.next, None);
+
+        let newer_page = FeedPage::new(page(false), Some(PageBound::After(cursor(1))));
+        assert_eq!(newer_page.next, Some(cursor(2).encode()));
+
+        let empty_page = Page::<PotentialArticle> {
+            items: vec![],
+            first: None,
+            last: None,
+            has_more: false,
+        };
+        let no_newer_page = FeedPage::new(empty_page, Some(PageBound::After(cursor(1))));
+        assert_eq!(no_newer_page.next, None);
+        assert_eq!(no_newer_page.previous, Some(cursor(1).encode()));
+    }
 }
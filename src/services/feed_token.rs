@@ -0,0 +1,41 @@
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+/// new_feed_token generates the secret of a user's public feed url,
+/// made of two random uuids, 244 random bits.
+pub fn new_feed_token() -> String {
+    format!("{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple())
+}
+
+/// hash_feed_token returns what is stored of a feed token: its sha256.
+/// Tokens themselves are only known to their user.
+pub fn hash_feed_token(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
+
+/// feed_token_url returns the public url of the feed of the user owning `token`
+pub fn feed_token_url(public_url: &str, token: &str) -> String {
+    format!(
+        "{}/panya/u/{}/feed.xml",
+        public_url.trim_end_matches('/'),
+        token
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_i_can_make_feed_tokens() {
+        let token = new_feed_token();
+        assert_eq!(token.len(), 64);
+        assert_ne!(token, new_feed_token());
+        assert_eq!(hash_feed_token(&token), hash_feed_token(&token));
+        assert_ne!(hash_feed_token(&token), token);
+        assert_eq!(
+            feed_token_url("https://panya.com/", "abc"),
+            "https://panya.com/panya/u/abc/feed.xml"
+        );
+    }
+}
@@ -0,0 +1,140 @@
+use reqwest::{
+    header::{HeaderMap, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED, LINK, LOCATION},
+    redirect::Policy,
+    StatusCode, Url,
+};
+
+use crate::{entities::channel::Channel, error::Error, services::scheduler::FetchScheduler};
+
+pub enum FetchOutcome {
+    NotModified,
+    Fetched {
+        body: Vec<u8>,
+        etag: Option<String>,
+        last_modified: Option<String>,
+        // the Link headers of the response, joined
+        link: Option<String>,
+    },
+}
+
+// redirects followed before giving up on a fetch
+const MAX_REDIRECTS: usize = 10;
+
+pub struct FetchResponse {
+    pub outcome: FetchOutcome,
+    // where the url was permanently moved to, when every redirect
+    // followed from it was permanent (301 or 308)
+    pub moved_to: Option<String>,
+}
+
+/// FetchError is a failed fetch, along with the HTTP status
+/// the source answered with, if it answered at all.
+#[derive(Debug)]
+pub struct FetchError {
+    pub error: Error,
+    pub http_status: Option<i32>,
+}
+
+impl From<Error> for FetchError {
+    fn from(error: Error) -> Self {
+        FetchError {
+            error,
+            http_status: None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for FetchError {
+    fn from(error: reqwest::Error) -> Self {
+        FetchError {
+            http_status: error.status().map(|status| status.as_u16() as i32),
+            error: error.into(),
+        }
+    }
+}
+
+impl From<FetchError> for Error {
+    fn from(fetch_error: FetchError) -> Self {
+        fetch_error.error
+    }
+}
+
+fn header_string(headers: &HeaderMap, name: impl reqwest::header::AsHeaderName) -> Option<String> {
+    headers
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string)
+}
+
+fn is_permanent_redirect(status: StatusCode) -> bool {
+    status == StatusCode::MOVED_PERMANENTLY || status == StatusCode::PERMANENT_REDIRECT
+}
+
+/// conditional_get fetches a channel's url, sending the `ETag` and `Last-Modified`
+/// validators stored on the channel, if any. A 304 response means nothing changed.
+/// Redirects are followed by hand, so that a chain of permanent ones can be reported.
+pub async fn conditional_get(
+    scheduler: &FetchScheduler,
+    channel: &Channel,
+) -> Result<FetchResponse, FetchError> {
+    let client = reqwest::Client::builder()
+        .redirect(Policy::none())
+        .build()?;
+    let mut url = Url::parse(&channel.url).map_err(|err| Error(err.to_string()))?;
+    let mut moved_to = None;
+    let mut is_permanent = true;
+
+    for _ in 0..=MAX_REDIRECTS {
+        let mut request = client.get(url.clone());
+        if let Some(etag) = &channel.etag {
+            request = request.header(IF_NONE_MATCH, etag);
+        }
+        if let Some(last_modified) = &channel.last_modified {
+            request = request.header(IF_MODIFIED_SINCE, last_modified);
+        }
+        // dropped at the end of each hop, the next one may go to the same host
+        let _permit = scheduler.acquire(url.as_str()).await;
+        let response = request.send().await?;
+        let status = response.status();
+        if status == StatusCode::NOT_MODIFIED {
+            return Ok(FetchResponse {
+                outcome: FetchOutcome::NotModified,
+                moved_to,
+            });
+        }
+        if status.is_redirection() {
+            let location =
+                header_string(response.headers(), LOCATION).ok_or_else(|| FetchError {
+                    error: Error(format!("redirect without location from {}", url)),
+                    http_status: Some(status.as_u16() as i32),
+                })?;
+            url = url.join(&location).map_err(|err| Error(err.to_string()))?;
+            is_permanent = is_permanent && is_permanent_redirect(status);
+            if is_permanent {
+                moved_to = Some(url.to_string());
+            }
+            continue;
+        }
+        let response = response.error_for_status()?;
+        let etag = header_string(response.headers(), ETAG);
+        let last_modified = header_string(response.headers(), LAST_MODIFIED);
+        let links = response
+            .headers()
+            .get_all(LINK)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .collect::<Vec<&str>>()
+            .join(", ");
+
+        return Ok(FetchResponse {
+            outcome: FetchOutcome::Fetched {
+                link: (!links.is_empty()).then_some(links),
+                body: response.bytes().await?.to_vec(),
+                etag,
+                last_modified,
+            },
+            moved_to,
+        });
+    }
+    Err(Error(format!("too many redirects from {}", channel.url)).into())
+}
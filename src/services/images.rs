@@ -0,0 +1,133 @@
+use scraper::{Html, Selector};
+use url::Url;
+
+/// ImageCandidate is an image an item links to, through an enclosure,
+/// a Media RSS element or an `<img>` tag of its description.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ImageCandidate {
+    pub url: String,
+    pub mime_type: Option<String>,
+    pub width: Option<u32>,
+    // the source tells this is an image without giving its mime type (media:thumbnail, <img>)
+    pub is_image: bool,
+}
+
+impl ImageCandidate {
+    pub fn new(url: &str, mime_type: Option<&str>, width: Option<&str>) -> Self {
+        let mime_type = mime_type
+            .map(|mime_type| mime_type.trim().to_lowercase())
+            .filter(|mime_type| !mime_type.is_empty());
+        ImageCandidate {
+            url: url.trim().to_string(),
+            is_image: mime_type
+                .as_ref()
+                .is_some_and(|mime_type| mime_type.starts_with("image/")),
+            mime_type,
+            width: width.and_then(|width| width.trim().parse().ok()),
+        }
+    }
+
+    /// image sets the candidate as an image, even though its mime type is unknown
+    pub fn image(mut self) -> Self {
+        self.is_image = self.is_image || self.mime_type.is_none();
+        self
+    }
+
+    /// is_other_media tells if the candidate is declared as a non-image media (audio, video...)
+    fn is_other_media(&self) -> bool {
+        self.mime_type.is_some() && !self.is_image
+    }
+}
+
+/// images_in_html lists the images of an html fragment, such as an item's description
+pub fn images_in_html(html: &str) -> Vec<ImageCandidate> {
+    let selector = match Selector::parse("img[src]") {
+        Ok(res) => res,
+        Err(_) => return vec![],
+    };
+    Html::parse_fragment(html)
+        .select(&selector)
+        .filter_map(|img| {
+            let src = img.value().attr("src")?;
+            Some(ImageCandidate::new(src, None, img.value().attr("width")).image())
+        })
+        .collect()
+}
+
+/// pick_image picks the best candidate: images with an image mime type come first,
+/// then the largest declared width, then the first one found. Declared non-image media
+/// and urls that are not http are ignored. The picked url is resolved against `base`.
+pub fn pick_image(candidates: &[ImageCandidate], base: &str) -> Option<String> {
+    let base = Url::parse(base).ok();
+    candidates
+        .iter()
+        .filter(|candidate| !candidate.is_other_media() && !candidate.url.is_empty())
+        .filter_map(|candidate| {
+            let url = match &base {
+                Some(base) => base.join(&candidate.url).ok()?,
+                None => Url::parse(&candidate.url).ok()?,
+            };
+            if url.scheme() != "http" && url.scheme() != "https" {
+                return None;
+            }
+            Some((url.to_string(), candidate))
+        })
+        .enumerate()
+        .max_by_key(|(idx, (_, candidate))| {
+            (
+                // other media being filtered out, a mime type is an image one
+                candidate.mime_type.is_some(),
+                candidate.width.unwrap_or_default(),
+                std::cmp::Reverse(*idx),
+            )
+        })
+        .map(|(_, (url, _))| url)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_i_can_find_images_in_html() {
+        let candidates = images_in_html(
+            r#"<p>Text <img src="/a.png" width="300"> and <img alt="no src"> <img src="b.jpg"></p>"#,
+        );
+
+        assert_eq!(candidates.len(), 2);
+        assert_eq!(candidates[0].url, "/a.png");
+        assert_eq!(candidates[0].width, Some(300));
+        assert!(candidates[0].is_image);
+        assert_eq!(candidates[1].width, None);
+    }
+
+    #[test]
+    fn test_i_can_pick_image() {
+        let base = "https://example.com/blog/feed.xml";
+        let candidates = vec![
+            ImageCandidate::new("/small.png", None, Some("100")).image(),
+            ImageCandidate::new("https://cdn.com/video.mp4", Some("video/mp4"), Some("1920")),
+            ImageCandidate::new("thumb.jpg", Some("image/jpeg"), Some("150")),
+            ImageCandidate::new("large.jpg", Some("IMAGE/JPEG"), Some("800")),
+        ];
+        assert_eq!(
+            pick_image(&candidates, base),
+            Some("https://example.com/blog/large.jpg".to_string())
+        );
+        assert_eq!(
+            pick_image(&candidates[..2], base),
+            Some("https://example.com/small.png".to_string())
+        );
+        assert_eq!(pick_image(&candidates[1..2], base), None);
+
+        let no_width = vec![
+            ImageCandidate::new("first.jpg", Some("image/jpeg"), None),
+            ImageCandidate::new("second.jpg", Some("image/jpeg"), None),
+            ImageCandidate::new("data:image/png;base64,AAAA", Some("image/png"), None),
+        ];
+        assert_eq!(
+            pick_image(&no_width, base),
+            Some("https://example.com/blog/first.jpg".to_string())
+        );
+    }
+}
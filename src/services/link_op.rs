@@ -25,6 +25,43 @@ pub fn trim_link(link: &str) -> String {
     )
 }
 
+/// canonical_link normalizes an item link so that the same article, linked with
+/// a different scheme, a "www" prefix, tracking parameters or a fragment, compares equal.
+pub fn canonical_link(link: &str) -> String {
+    let mut url = match Url::parse(link.trim()) {
+        Ok(res) => res,
+        Err(_) => return link.trim().to_string(),
+    };
+    url.set_fragment(None);
+    let query: Vec<(String, String)> = url
+        .query_pairs()
+        .filter(|(key, _)| !key.starts_with("utm_"))
+        .map(|(key, value)| (key.into_owned(), value.into_owned()))
+        .collect();
+    if query.is_empty() {
+        url.set_query(None);
+    } else {
+        url.query_pairs_mut().clear().extend_pairs(query);
+    }
+    let host = url.host_str().unwrap_or_default();
+    let host = host.strip_prefix("www.").unwrap_or(host).to_string();
+    let path = url.path().trim_end_matches('/').to_string();
+
+    match url.query() {
+        Some(query) => format!("{}{}?{}", host, path, query),
+        None => format!("{}{}", host, path),
+    }
+}
+
+/// resolve_link resolves a link found in a page or a document against its url,
+/// dropping the ones that are not http.
+pub fn resolve_link(base: &Url, href: &str) -> Option<String> {
+    base.join(href.trim())
+        .ok()
+        .filter(|url| url.scheme() == "http" || url.scheme() == "https")
+        .map(|url| url.to_string())
+}
+
 fn clean_url(input: &str) -> String {
     // Parse the input string as a URL
     let parsed_url = Url::parse(&format!("https://{}", input)).expect("Invalid URL");
@@ -74,4 +111,22 @@ mod tests {
             assert_eq!(clean_url(url[0]), url[1]);
         }
     }
+
+    #[test]
+    fn test_i_can_canonicalize_links() {
+        let links = [
+            ["https://www.example.com/a/", "example.com/a"],
+            ["http://example.com/a#comments", "example.com/a"],
+            [
+                "https://example.com/a?id=3&utm_source=rss&utm_medium=feed",
+                "example.com/a?id=3",
+            ],
+            ["https://Example.com/a?utm_source=rss", "example.com/a"],
+            ["not an url", "not an url"],
+        ];
+
+        for link in links {
+            assert_eq!(canonical_link(link[0]), link[1]);
+        }
+    }
 }
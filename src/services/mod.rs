@@ -1,11 +1,23 @@
 pub mod bakery;
 pub mod channels;
+pub mod cook_atom;
+pub mod cook_json_feed;
 pub mod cook_rss;
+pub mod discovery;
+pub mod extract;
 pub mod feed;
+pub mod feed_token;
+pub mod fetch;
 pub mod grpc;
+pub mod images;
 pub mod link_op;
+pub mod opml;
 pub mod panya;
+pub mod redirect;
 pub mod refresh;
 pub mod request_rss;
+pub mod scheduler;
+pub mod sources;
 pub mod token;
 pub mod vec;
+pub mod websub;
@@ -0,0 +1,457 @@
+use std::io::Cursor;
+
+use quick_xml::{
+    escape::unescape,
+    events::{BytesDecl, BytesEnd, BytesStart, BytesText, Event},
+    Reader, Writer,
+};
+use serde::Serialize;
+
+use crate::{
+    config::Settings,
+    db::{channel::Channels, model::CollectionModel},
+    entities::{
+        channel::{new_with_seq_db, Channel, SourceType},
+        user::Folder,
+    },
+    error::Error,
+    handlers::public_entities::public_channel::PublicChannel,
+    services::{
+        channels::find_out_source_type, link_op::trim_link, scheduler::FetchScheduler,
+        sources::registry,
+    },
+};
+
+/// OpmlOutline is a subscription found in an OPML document
+#[derive(Debug, Clone, PartialEq)]
+pub struct OpmlOutline {
+    pub title: Option<String>,
+    pub url: String,
+    pub outline_type: Option<String>,
+    // folders the outline is nested in, joined with "/"
+    pub folder: Option<String>,
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum OutlineStatus {
+    Created,
+    Existing,
+    Failed,
+}
+
+#[derive(Debug, Serialize)]
+pub struct OutlineReport {
+    pub url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub folder: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub channel_id: Option<i32>,
+    pub status: OutlineStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// outline_type returns the OPML outline type of a source type.
+/// Bakery channels are web pages, hence exported as links.
+fn outline_type(source_type: SourceType) -> &'static str {
+    match source_type {
+        SourceType::RSSFeed => "rss",
+        SourceType::AtomFeed => "atom",
+        SourceType::JsonFeed => "json",
+        SourceType::Bakery => "link",
+        SourceType::Other(name) => name,
+    }
+}
+
+/// source_type_hint returns the source type an outline type stands for, if it is explicit.
+/// "rss" is not, most readers using it for any kind of feed.
+fn source_type_hint(outline_type: Option<&str>) -> Option<SourceType> {
+    match outline_type?.to_lowercase().as_str() {
+        "atom" => Some(SourceType::AtomFeed),
+        "json" | "jsonfeed" => Some(SourceType::JsonFeed),
+        "link" => Some(SourceType::Bakery),
+        name => registry()
+            .find(name)
+            .filter(|source_type| matches!(source_type, SourceType::Other(_))),
+    }
+}
+
+fn attribute(element: &BytesStart, name: &str) -> Result<Option<String>, Error> {
+    Ok(match element.try_get_attribute(name)? {
+        Some(attr) => Some(
+            unescape(&String::from_utf8_lossy(&attr.value))
+                .map_err(quick_xml::Error::from)?
+                .trim()
+                .to_string(),
+        )
+        .filter(|v| !v.is_empty()),
+        None => None,
+    })
+}
+
+/// outline_title returns the `text` attribute of an outline, falling back on its `title`
+fn outline_title(element: &BytesStart) -> Result<Option<String>, Error> {
+    Ok(attribute(element, "text")?.or(attribute(element, "title")?))
+}
+
+/// outline_from returns the subscription an outline element describes:
+/// a feed through its `xmlUrl`, or a web page through the `url` of a "link" outline.
+/// `None` is returned for folders.
+fn outline_from(
+    element: &BytesStart,
+    folders: &[Option<String>],
+) -> Result<Option<OpmlOutline>, Error> {
+    let outline_type = attribute(element, "type")?;
+    let url = match attribute(element, "xmlUrl")? {
+        Some(url) => url,
+        None if outline_type.as_deref() == Some("link") => match attribute(element, "url")? {
+            Some(url) => url,
+            None => return Ok(None),
+        },
+        None => return Ok(None),
+    };
+    let folder = folders.iter().flatten().cloned().collect::<Vec<String>>();
+
+    Ok(Some(OpmlOutline {
+        title: outline_title(element)?,
+        url,
+        outline_type,
+        folder: (!folder.is_empty()).then(|| folder.join("/")),
+    }))
+}
+
+/// parse_opml lists the subscriptions of an OPML document, along with the folders they are in.
+pub fn parse_opml(raw: &str) -> Result<Vec<OpmlOutline>, Error> {
+    let mut reader = Reader::from_str(raw);
+    let mut outlines = vec![];
+    let mut is_opml = false;
+    // outlines currently open: the folder's name, or None for subscriptions
+    let mut folders: Vec<Option<String>> = vec![];
+
+    loop {
+        match reader.read_event()? {
+            Event::Start(e) if e.name().as_ref() == b"opml" => is_opml = true,
+            Event::Start(e) if e.name().as_ref() == b"outline" => {
+                match outline_from(&e, &folders)? {
+                    Some(outline) => {
+                        outlines.push(outline);
+                        folders.push(None);
+                    }
+                    None => folders.push(outline_title(&e)?),
+                }
+            }
+            Event::Empty(e) if e.name().as_ref() == b"outline" => {
+                if let Some(outline) = outline_from(&e, &folders)? {
+                    outlines.push(outline);
+                }
+            }
+            Event::End(e) if e.name().as_ref() == b"outline" => {
+                folders.pop();
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    if !is_opml {
+        return Error::str_to_result("not an OPML document");
+    }
+    Ok(outlines)
+}
+
+fn write<'a>(
+    writer: &mut Writer<Cursor<Vec<u8>>>,
+    event: impl Into<Event<'a>>,
+) -> Result<(), Error> {
+    writer
+        .write_event(event)
+        .map_err(|err| Error(err.to_string()))
+}
+
+fn write_outline(
+    writer: &mut Writer<Cursor<Vec<u8>>>,
+    channel: &PublicChannel,
+) -> Result<(), Error> {
+    let outline_type = outline_type(channel.source_type.unwrap_or(SourceType::Bakery));
+    let url_attribute = if outline_type == "link" {
+        "url"
+    } else {
+        "xmlUrl"
+    };
+
+    write(
+        writer,
+        Event::Empty(BytesStart::new("outline").with_attributes([
+            ("type", outline_type),
+            ("text", channel.name.as_str()),
+            ("title", channel.name.as_str()),
+            (url_attribute, channel.url.as_str()),
+        ])),
+    )
+}
+
+/// FolderOutline is a folder of the rendered document, along with its subfolders
+struct FolderOutline<'a> {
+    name: &'a str,
+    channels: Vec<&'a PublicChannel>,
+    folders: Vec<FolderOutline<'a>>,
+}
+
+impl<'a> FolderOutline<'a> {
+    /// insert files `channels` in the folder found at `path`, creating the folders on the way
+    fn insert(folders: &mut Vec<Self>, path: &[&'a str], channels: Vec<&'a PublicChannel>) {
+        let (name, path) = match path.split_first() {
+            Some(res) => res,
+            None => return,
+        };
+        let index = match folders.iter().position(|folder| folder.name == *name) {
+            Some(index) => index,
+            None => {
+                folders.push(FolderOutline {
+                    name,
+                    channels: vec![],
+                    folders: vec![],
+                });
+                folders.len() - 1
+            }
+        };
+        if path.is_empty() {
+            folders[index].channels.extend(channels);
+        } else {
+            Self::insert(&mut folders[index].folders, path, channels);
+        }
+    }
+
+    fn write(&self, writer: &mut Writer<Cursor<Vec<u8>>>) -> Result<(), Error> {
+        write(
+            writer,
+            Event::Start(
+                BytesStart::new("outline")
+                    .with_attributes([("text", self.name), ("title", self.name)]),
+            ),
+        )?;
+        for channel in &self.channels {
+            write_outline(writer, channel)?;
+        }
+        for folder in &self.folders {
+            folder.write(writer)?;
+        }
+        write(writer, Event::End(BytesEnd::new("outline")))
+    }
+}
+
+/// render_opml renders channels as an OPML document. Channels found in `folders`
+/// are nested in their folder's outline, the others are listed at the root of the body.
+/// Folder names are paths, as imported by parse_opml, "A/B" being rendered as a folder B
+/// within a folder A.
+pub fn render_opml(
+    title: &str,
+    channels: &[PublicChannel],
+    folders: &[Folder],
+) -> Result<String, Error> {
+    let mut writer = Writer::new_with_indent(Cursor::new(Vec::new()), b' ', 2);
+
+    write(
+        &mut writer,
+        Event::Decl(BytesDecl::new("1.0", Some("UTF-8"), None)),
+    )?;
+    write(
+        &mut writer,
+        Event::Start(BytesStart::new("opml").with_attributes([("version", "2.0")])),
+    )?;
+    write(&mut writer, Event::Start(BytesStart::new("head")))?;
+    write(&mut writer, Event::Start(BytesStart::new("title")))?;
+    write(&mut writer, Event::Text(BytesText::new(title)))?;
+    write(&mut writer, Event::End(BytesEnd::new("title")))?;
+    write(&mut writer, Event::End(BytesEnd::new("head")))?;
+    write(&mut writer, Event::Start(BytesStart::new("body")))?;
+
+    for channel in channels
+        .iter()
+        .filter(|channel| !folders.iter().any(|f| f.channel_ids.contains(&channel.id)))
+    {
+        write_outline(&mut writer, channel)?;
+    }
+    let mut folder_outlines = vec![];
+    for folder in folders {
+        let folder_channels = channels
+            .iter()
+            .filter(|channel| folder.channel_ids.contains(&channel.id))
+            .collect::<Vec<&PublicChannel>>();
+        if folder_channels.is_empty() {
+            continue;
+        }
+        let mut path = folder
+            .name
+            .split('/')
+            .filter(|name| !name.is_empty())
+            .collect::<Vec<&str>>();
+        if path.is_empty() {
+            path.push(&folder.name);
+        }
+        FolderOutline::insert(&mut folder_outlines, &path, folder_channels);
+    }
+    for folder in &folder_outlines {
+        folder.write(&mut writer)?;
+    }
+
+    write(&mut writer, Event::End(BytesEnd::new("body")))?;
+    write(&mut writer, Event::End(BytesEnd::new("opml")))?;
+    String::from_utf8(writer.into_inner().into_inner()).map_err(|err| Error(err.to_string()))
+}
+
+/// resolve_source_type finds out the source type of an outline. Outlines having an `xmlUrl`
+/// are feeds, so a source that could not be identified is assumed to be RSS.
+async fn resolve_source_type(scheduler: &FetchScheduler, outline: &OpmlOutline) -> SourceType {
+    if let Some(source_type) = source_type_hint(outline.outline_type.as_deref()) {
+        return source_type;
+    }
+    match find_out_source_type(scheduler, &outline.url).await {
+        Ok(SourceType::Bakery) | Err(_) => SourceType::RSSFeed,
+        Ok(source_type) => source_type,
+    }
+}
+
+/// import_outlines finds or creates the channel of each outline.
+/// The source types are resolved concurrently, while the channels are created
+/// one at a time, so an OPML document listing a feed twice does not create it twice.
+pub async fn import_outlines(
+    channels_coll: &Channels<'_, Channel>,
+    scheduler: &FetchScheduler,
+    settings: &Settings,
+    outlines: Vec<OpmlOutline>,
+) -> Vec<OutlineReport> {
+    let source_types = futures::future::join_all(
+        outlines
+            .iter()
+            .map(|outline| resolve_source_type(scheduler, outline)),
+    )
+    .await;
+    let mut reports = vec![];
+
+    for (outline, source_type) in outlines.into_iter().zip(source_types) {
+        let name = trim_link(&outline.url);
+        let res = match channels_coll.find_one("name", &name).await {
+            Some(channel) => Ok((channel, OutlineStatus::Existing)),
+            None => new_with_seq_db(&name, &outline.url, source_type, channels_coll, settings)
+                .await
+                .map(|channel| (channel, OutlineStatus::Created)),
+        };
+        let (channel_id, status, error) = match res {
+            Ok((channel, status)) => (Some(channel.id), status, None),
+            Err(err) => (None, OutlineStatus::Failed, Some(err.to_string())),
+        };
+        reports.push(OutlineReport {
+            url: outline.url,
+            title: outline.title,
+            folder: outline.folder,
+            channel_id,
+            status,
+            error,
+        });
+    }
+    reports
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OPML: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<opml version="2.0">
+  <head><title>Subscriptions</title></head>
+  <body>
+    <outline type="rss" text="Root feed" xmlUrl="https://example.com/feed.xml" htmlUrl="https://example.com"/>
+    <outline text="News">
+      <outline type="atom" title="Atom &amp; co" xmlUrl="https://news.com/atom"/>
+      <outline text="Local">
+        <outline text="Town" xmlUrl="https://town.com/rss"></outline>
+      </outline>
+    </outline>
+    <outline type="link" text="Page" url="https://page.com/"/>
+    <outline text="Empty folder"/>
+  </body>
+</opml>"#;
+
+    #[test]
+    fn test_i_can_parse_opml() {
+        let outlines = parse_opml(OPML).unwrap();
+
+        assert_eq!(outlines.len(), 4);
+        assert_eq!(
+            outlines[0],
+            OpmlOutline {
+                title: Some("Root feed".to_string()),
+                url: "https://example.com/feed.xml".to_string(),
+                outline_type: Some("rss".to_string()),
+                folder: None,
+            }
+        );
+        assert_eq!(outlines[1].title, Some("Atom & co".to_string()));
+        assert_eq!(outlines[1].folder, Some("News".to_string()));
+        assert_eq!(outlines[2].url, "https://town.com/rss");
+        assert_eq!(outlines[2].folder, Some("News/Local".to_string()));
+        assert_eq!(outlines[3].url, "https://page.com/");
+        assert_eq!(outlines[3].folder, None);
+        assert_eq!(
+            source_type_hint(outlines[1].outline_type.as_deref()),
+            Some(SourceType::AtomFeed)
+        );
+        assert_eq!(source_type_hint(outlines[0].outline_type.as_deref()), None);
+
+        assert!(parse_opml("<rss><channel></channel></rss>").is_err());
+    }
+
+    #[test]
+    fn test_i_can_render_opml() {
+        let channels = PublicChannel::from_channels(vec![
+            Channel {
+                id: 1,
+                ..Channel::new("a.com", "https://a.com/feed", SourceType::RSSFeed, 1000)
+            },
+            Channel {
+                id: 2,
+                ..Channel::new(
+                    "b.com",
+                    "https://b.com/atom?x=1&y=2",
+                    SourceType::AtomFeed,
+                    1000,
+                )
+            },
+            Channel {
+                id: 3,
+                ..Channel::new("c.com", "https://c.com/", SourceType::Bakery, 1000)
+            },
+            Channel {
+                id: 4,
+                ..Channel::new("d.com", "https://d.com/rss", SourceType::RSSFeed, 1000)
+            },
+        ]);
+        let folders = vec![
+            Folder {
+                name: "News/Local".to_string(),
+                channel_ids: vec![4],
+            },
+            Folder {
+                name: "News".to_string(),
+                channel_ids: vec![2],
+            },
+        ];
+        let rendered = render_opml("Pan-Ya", &channels, &folders).unwrap();
+
+        assert!(rendered.contains(r#"xmlUrl="https://b.com/atom?x=1&amp;y=2""#));
+        let outlines = parse_opml(&rendered).unwrap();
+        assert_eq!(outlines.len(), 4);
+        assert_eq!(outlines[0].url, "https://a.com/feed");
+        assert_eq!(outlines[0].folder, None);
+        assert_eq!(outlines[1].url, "https://c.com/");
+        assert_eq!(outlines[1].outline_type, Some("link".to_string()));
+        assert_eq!(outlines[2].url, "https://b.com/atom?x=1&y=2");
+        assert_eq!(outlines[2].folder, Some("News".to_string()));
+        assert_eq!(outlines[3].url, "https://d.com/rss");
+        assert_eq!(outlines[3].folder, Some("News/Local".to_string()));
+        assert_eq!(rendered.matches(r#"<outline text="News""#).count(), 1);
+    }
+}
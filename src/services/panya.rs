@@ -1,13 +1,21 @@
+use std::sync::Arc;
+
 use crate::{
+    config::Settings,
     db::{
-        entities::Timer,
+        channel::Channels,
         items::Items,
-        model::{BlankCollection, CollectionModel, SortOrder},
+        model::{CollectionModel, SortOrder},
+        mongo::Handle,
+        timers::Timers,
     },
-    entities::potential_articles::PotentialArticle,
-    utils::now_minus_minutes,
+    entities::{channel::Channel, potential_articles::PotentialArticle},
+    services::{refresh::refresh_channel, scheduler::FetchScheduler},
+    utils::now_timestamp_ms,
 };
+use chrono::Utc;
 use mongodb::bson::doc;
+use rocket::tokio::spawn;
 
 /// return_db_articles fetch a `limit` amount of items from db,
 /// then turn them into XML.
@@ -28,31 +36,56 @@ pub async fn return_db_articles(
         .unwrap_or(vec![])
 }
 
-/// should_fetch_items assert if items should be fetched or not,
-/// with respect to the update date of the
-pub async fn should_fetch_items(
-    timers: &BlankCollection<'_, Timer>,
-    channel: &str,
-    cooldown: i64,
-) -> bool {
-    timers
-        .find(
-            doc! {
-                "channel": channel,
-                "update_date": {
-                    "$gt": now_minus_minutes(cooldown),
-                },
-            },
-            Some("create_date"),
-            SortOrder::DESC,
-            1,
-        )
-        .await
-        .and_then(|v| {
-            if v.is_empty() {
-                return None;
+/// trigger_bakery refreshes a channel that is not polled, such as a Bakery one, in the
+/// background, unless it was already refreshed within the `bakery_trigger_cooldown` (minutes),
+/// or is backing off after failing refreshes. The refresh is reserved on the channel first,
+/// so concurrent calls do not scrape it twice. Every attempt, failed or not, is recorded
+/// in the timers collection along with the time it took.
+pub fn trigger_bakery(
+    handle: Arc<Handle>,
+    scheduler: Arc<FetchScheduler>,
+    settings: Settings,
+    channel: Channel,
+) {
+    if !channel.health.is_ready(Utc::now().timestamp()) {
+        return;
+    }
+    spawn(async move {
+        let channels_coll = match Channels::<Channel>::new(&handle, "panya") {
+            Ok(c) => c,
+            Err(err) => {
+                error!("Channels::new - can't open connection to db panya: {}", err);
+                return;
+            }
+        };
+        let timers = match Timers::new(&handle, "panya", "timers") {
+            Ok(c) => c,
+            Err(err) => {
+                error!("Timers::new - can't open connection to db panya: {}", err);
+                return;
+            }
+        };
+        match channels_coll
+            .reserve_refresh(channel.id, settings.bakery_trigger_cooldown * 60)
+            .await
+        {
+            Ok(true) => (),
+            Ok(false) => return,
+            Err(err) => {
+                error!("could not reserve the refresh of {}: {}", channel.name, err);
+                return;
             }
-            Some(())
-        })
-        .is_none()
+        }
+        let time_before = now_timestamp_ms();
+        match refresh_channel(&handle, &scheduler, &settings, &channel).await {
+            Ok(report) => info!(
+                "bakery refreshed {}: {} new items in {}ms",
+                channel.name, report.inserted, report.duration_ms
+            ),
+            Err(err) => error!("could not refresh {} through bakery: {}", channel.name, err),
+        }
+        timers
+            .insert_one(&channel.name, (now_timestamp_ms() - time_before) as i64)
+            .await;
+    });
 }
@@ -0,0 +1,128 @@
+use mongodb::bson::doc;
+
+use crate::{
+    config::Settings,
+    db::{channel::Channels, items::Items, model::CollectionModel, mongo::Handle, user::Users},
+    entities::{
+        channel::{Channel, Redirect},
+        potential_articles::PotentialArticle,
+        user::User,
+    },
+    error::Error,
+    services::link_op::trim_link,
+};
+
+/// next_redirect counts a new observation of a permanent redirect to `location`.
+/// Observations of another location start the count over.
+pub fn next_redirect(current: Option<&Redirect>, location: &str) -> Redirect {
+    Redirect {
+        location: location.to_string(),
+        observations: match current {
+            Some(redirect) if redirect.location == location => redirect.observations + 1,
+            _ => 1,
+        },
+    }
+}
+
+/// move_channel moves a channel to `url`. Its name follows the url, unless it was renamed.
+/// When another channel already lives at that url or name, the channel is merged into it:
+/// its items and subscribers are moved to the other channel, then it is deleted.
+pub async fn move_channel(handle: &Handle, channel: &Channel, url: &str) -> Result<(), Error> {
+    let channels_coll = Channels::new(handle, "panya")?;
+    let items_coll = Items::<PotentialArticle>::new(handle, "panya")?;
+    let users_coll = Users::<User>::new(handle, "panya")?;
+    let name = match channel.name == trim_link(&channel.url) {
+        true => trim_link(url),
+        false => channel.name.clone(),
+    };
+    let target = channels_coll
+        .find(
+            doc! {
+                "$or": [{"name": &name}, {"url": url}],
+                "id": {"$ne": channel.id},
+            },
+            Some("id"),
+            None,
+            1,
+        )
+        .await
+        .and_then(|channels| channels.into_iter().next());
+
+    match target {
+        Some(target) => {
+            let moved = items_coll.move_to_channel(channel.id, &target).await?;
+            let users = users_coll.replace_channel(channel.id, target.id).await?;
+            channels_coll.delete_one("id", channel.id).await?;
+            println!(
+                "[INFO] CHANNEL {} merged into CHANNEL {} ({}): {} items, {} users moved",
+                channel.id,
+                target.id,
+                target.url,
+                moved.inserted + moved.updated,
+                users
+            );
+        }
+        None => {
+            channels_coll
+                .update_location(channel.id, url, &name)
+                .await?;
+            if name != channel.name {
+                items_coll.rename_channel(channel.id, &name).await?;
+            }
+            println!(
+                "[INFO] CHANNEL {} moved from {} to {}",
+                channel.id, channel.url, url
+            );
+        }
+    }
+    Ok(())
+}
+
+/// record_redirect records whether the channel's url was permanently redirected to
+/// `moved_to` on its last fetch. Once the same redirect was observed
+/// `permanent_redirect_threshold` times in a row, the channel is moved to its new url,
+/// which is returned.
+pub async fn record_redirect(
+    handle: &Handle,
+    settings: &Settings,
+    channel: &Channel,
+    moved_to: Option<&str>,
+) -> Result<Option<String>, Error> {
+    let channels_coll = Channels::new(handle, "panya")?;
+    let location = match moved_to.filter(|location| *location != channel.url) {
+        Some(res) => res,
+        None => {
+            if channel.redirect.is_some() {
+                channels_coll.update_redirect(channel.id, None).await;
+            }
+            return Ok(None);
+        }
+    };
+    let redirect = next_redirect(channel.redirect.as_ref(), location);
+    if redirect.observations < settings.permanent_redirect_threshold {
+        channels_coll
+            .update_redirect(channel.id, Some(&redirect))
+            .await;
+        return Ok(None);
+    }
+    move_channel(handle, channel, location).await?;
+    Ok(Some(location.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_i_can_count_redirects() {
+        let redirect = next_redirect(None, "https://new.com/feed");
+        assert_eq!(redirect.observations, 1);
+
+        let redirect = next_redirect(Some(&redirect), "https://new.com/feed");
+        assert_eq!(redirect.observations, 2);
+
+        let redirect = next_redirect(Some(&redirect), "https://other.com/feed");
+        assert_eq!(redirect.location, "https://other.com/feed");
+        assert_eq!(redirect.observations, 1);
+    }
+}
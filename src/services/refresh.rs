@@ -1,9 +1,336 @@
-use crate::db::{
-    entities::Refresh,
-    model::BlankCollection,
+use std::collections::HashSet;
+
+use chrono::Utc;
+use futures::{stream, StreamExt};
+use mongodb::bson::doc;
+use serde::Serialize;
+
+use crate::{
+    config::Settings,
+    db::{
+        channel::Channels,
+        entities::Refresh,
+        items::Items,
+        model::{BlankCollection, CollectionModel, UpsertManyResult},
+        mongo::Handle,
+    },
+    entities::{
+        channel::{Channel, ChannelHealth},
+        potential_articles::PotentialArticle,
+    },
+    error::Error,
+    services::{
+        channels::{compute_refresh_frequency, record_failure},
+        extract::fetch_content,
+        fetch::{FetchError, FetchOutcome},
+        redirect::record_redirect,
+        scheduler::FetchScheduler,
+        sources::registry,
+        websub::{discover_hub, request_subscription, WebSubLinks},
+    },
+    utils::now_timestamp_ms,
 };
 
 pub type Refresher<'a> = BlankCollection<'a, Refresh>;
 
-impl<'a> Refresher<'a> {
+// number of article pages downloaded at once when extracting their content
+const EXTRACT_CONCURRENCY: usize = 4;
+
+#[derive(Debug, Serialize)]
+pub struct RefreshReport {
+    pub channel_id: i32,
+    pub fetched: usize,
+    pub inserted: usize,
+    pub updated: usize,
+    pub not_modified: bool,
+    pub refresh_frequency: i32,
+    // the url the channel was moved to, following a permanent redirect
+    pub moved_to: Option<String>,
+    pub duration_ms: u128,
+}
+
+/// FetchedSource is what came out of fetching a channel's source
+struct FetchedSource {
+    // `None` when the source did not change since the last fetch
+    articles: Option<Vec<PotentialArticle>>,
+    // the location the source was permanently moved to
+    moved_to: Option<String>,
+    // the WebSub hub the source advertises
+    hub: Option<WebSubLinks>,
+}
+
+/// parse_articles turns a fetched document into articles, according to the channel's source type
+fn parse_articles(channel: &Channel, body: &[u8]) -> Result<Vec<PotentialArticle>, Error> {
+    registry().get(channel.source_type)?.parse(channel, body)
+}
+
+/// fetch_articles downloads the channel's source and turns it into articles,
+/// through the fetcher registered for its source type.
+async fn fetch_articles(
+    channels_coll: &Channels<'_, Channel>,
+    scheduler: &FetchScheduler,
+    settings: &Settings,
+    channel: &Channel,
+) -> Result<FetchedSource, FetchError> {
+    let response = registry()
+        .get(channel.source_type)?
+        .fetch(scheduler, settings, channel)
+        .await?;
+    match response.outcome {
+        FetchOutcome::NotModified => {
+            channels_coll
+                .update_validators(channel.id, None, None, true)
+                .await;
+            Ok(FetchedSource {
+                articles: None,
+                moved_to: response.moved_to,
+                hub: None,
+            })
+        }
+        FetchOutcome::Fetched {
+            body,
+            etag,
+            last_modified,
+            link,
+        } => {
+            let articles = parse_articles(channel, &body)?;
+            // validators are only stored once the document could be parsed,
+            // so a broken document is not skipped by the next conditional fetch.
+            channels_coll
+                .update_validators(channel.id, etag.as_deref(), last_modified.as_deref(), false)
+                .await;
+            Ok(FetchedSource {
+                articles: Some(articles),
+                hub: discover_hub(channel.source_type, &body, link.as_deref(), &channel.url),
+                moved_to: response.moved_to,
+            })
+        }
+    }
+}
+
+/// extract_new_articles fills the articles that are not stored yet with the main content
+/// of their page. Articles whose page could not be fetched are kept without content.
+async fn extract_new_articles(
+    items_coll: &Items<'_, PotentialArticle>,
+    scheduler: &FetchScheduler,
+    channel: &Channel,
+    articles: Vec<PotentialArticle>,
+) -> Vec<PotentialArticle> {
+    let keys: Vec<&str> = articles
+        .iter()
+        .map(|article| article.key.as_str())
+        .collect();
+    let stored: HashSet<String> = items_coll
+        .find(
+            doc! {"channel_id": channel.id, "key": {"$in": keys}},
+            None,
+            None,
+            None,
+        )
+        .await
+        .unwrap_or_default()
+        .into_iter()
+        .map(|article| article.key)
+        .collect();
+
+    stream::iter(articles)
+        .map(|mut article| {
+            let is_new = !stored.contains(&article.key);
+            async move {
+                if !is_new {
+                    return article;
+                }
+                match fetch_content(scheduler, &article.link).await {
+                    Ok(Some(content)) => {
+                        article.content_html = Some(content.html);
+                        article.content_text = Some(content.text);
+                    }
+                    Ok(None) => {}
+                    Err(err) => eprintln!(
+                        "[ERR ] Could not extract the content of {}: {}",
+                        article.link, err
+                    ),
+                }
+                article
+            }
+        })
+        .buffered(EXTRACT_CONCURRENCY)
+        .collect()
+        .await
+}
+
+/// store_articles upserts the articles in the items collection,
+/// skipping the ones sharing the key of a previous article of the batch.
+pub async fn store_articles(
+    items_coll: &Items<'_, PotentialArticle>,
+    articles: Vec<PotentialArticle>,
+) -> Result<UpsertManyResult, Error> {
+    let mut keys = HashSet::new();
+    let articles: Vec<PotentialArticle> = articles
+        .into_iter()
+        .filter(|article| keys.insert(article.key.clone()))
+        .collect();
+    if articles.is_empty() {
+        return Ok(UpsertManyResult::default());
+    }
+    items_coll.upsert_many(&articles).await
+}
+
+/// subscribe_to_hub subscribes the channel to the hub its source advertises,
+/// unless it already did subscribe to it, or WebSub is disabled.
+async fn subscribe_to_hub(
+    handle: &Handle,
+    scheduler: &FetchScheduler,
+    settings: &Settings,
+    channel: &Channel,
+    hub: &WebSubLinks,
+) {
+    let subscribed = channel
+        .websub
+        .as_ref()
+        .is_some_and(|websub| websub.hub == hub.hub && websub.topic == hub.topic);
+    if !settings.websub_enabled() || subscribed {
+        return;
+    }
+    match request_subscription(handle, scheduler, settings, channel, hub).await {
+        Ok(()) => println!(
+            "[INFO] CHANNEL {} ({}) requested a subscription to hub {}",
+            channel.id, channel.url, hub.hub
+        ),
+        Err(err) => eprintln!(
+            "[ERR ] CHANNEL {} ({}) could not subscribe to hub {}: {}",
+            channel.id, channel.url, hub.hub, err
+        ),
+    }
+}
+
+/// ingest_document stores the items of a document pushed by a channel's hub,
+/// the same way as if it had been fetched.
+pub async fn ingest_document(
+    handle: &Handle,
+    scheduler: &FetchScheduler,
+    channel: &Channel,
+    body: &[u8],
+) -> Result<UpsertManyResult, Error> {
+    let channels_coll = Channels::new(handle, "panya")?;
+    let items_coll = Items::<PotentialArticle>::new(handle, "panya")?;
+    let articles = parse_articles(channel, body)?;
+    let articles = if channel.extract_content {
+        extract_new_articles(&items_coll, scheduler, channel, articles).await
+    } else {
+        articles
+    };
+    let res = store_articles(&items_coll, articles).await;
+    channels_coll
+        .update_refresh(channel.id, None, res.is_ok())
+        .await;
+    res
+}
+
+/// refresh_channel fetches a channel, stores its new items and
+/// updates its refresh dates, whether the fetch succeeded or not.
+/// On success, the channel's refresh frequency is adapted to its publish rate
+/// and its health is reset. On failure, the failure is recorded in its health.
+pub async fn refresh_channel(
+    handle: &Handle,
+    scheduler: &FetchScheduler,
+    settings: &Settings,
+    channel: &Channel,
+) -> Result<RefreshReport, Error> {
+    let channels_coll = Channels::new(handle, "panya")?;
+    let items_coll = Items::<PotentialArticle>::new(handle, "panya")?;
+    let time_before = now_timestamp_ms();
+
+    // only the source's failures are recorded in the channel's health,
+    // not the ones of storing its articles
+    let fetch_res = fetch_articles(&channels_coll, scheduler, settings, channel).await;
+    match &fetch_res {
+        Err(err) => {
+            let health = record_failure(
+                &channel.health,
+                &err.error.to_string(),
+                err.http_status,
+                Utc::now().timestamp(),
+                settings,
+            );
+            if health.suspended && !channel.health.suspended {
+                eprintln!(
+                    "[ERR ] CHANNEL {} ({}) suspended after {} consecutive failures",
+                    channel.id, channel.url, health.consecutive_failures
+                );
+            }
+            channels_coll.update_health(channel.id, &health).await;
+        }
+        Ok(_) if !channel.health.is_healthy() => {
+            channels_coll
+                .update_health(channel.id, &ChannelHealth::default())
+                .await;
+        }
+        Ok(_) => {}
+    }
+    let (fetch_res, moved_to) = match fetch_res {
+        Ok(fetched) => {
+            if let Some(hub) = &fetched.hub {
+                subscribe_to_hub(handle, scheduler, settings, channel, hub).await;
+            }
+            (Ok(fetched.articles), fetched.moved_to)
+        }
+        Err(err) => (Err(err), None),
+    };
+    let res = match fetch_res {
+        Ok(Some(articles)) => {
+            let fetched = articles.len();
+            let articles = if channel.extract_content {
+                extract_new_articles(&items_coll, scheduler, channel, articles).await
+            } else {
+                articles
+            };
+            store_articles(&items_coll, articles)
+                .await
+                .map(|stored| (fetched, stored, false))
+        }
+        Ok(None) => Ok((0, UpsertManyResult::default(), true)),
+        Err(err) => Err(err.into()),
+    };
+    channels_coll
+        .update_refresh(channel.id, None, res.is_ok())
+        .await;
+    let (fetched, stored, not_modified) = res?;
+
+    let (min, max) = settings.refresh_frequency_bounds();
+    let refresh_frequency = compute_refresh_frequency(
+        channel.refresh_frequency,
+        channel
+            .base_refresh_frequency
+            .unwrap_or(settings.base_refresh_frequency),
+        stored.inserted,
+        min,
+        max,
+    );
+    if refresh_frequency != channel.refresh_frequency {
+        channels_coll
+            .update_refresh_frequency(channel.id, refresh_frequency)
+            .await;
+    }
+    // moving the channel comes last, as it may be merged into another one and deleted
+    let moved_to = record_redirect(handle, settings, channel, moved_to.as_deref())
+        .await
+        .unwrap_or_else(|err| {
+            eprintln!(
+                "[ERR ] Could not move CHANNEL {} ({}): {}",
+                channel.id, channel.url, err
+            );
+            None
+        });
+
+    Ok(RefreshReport {
+        channel_id: channel.id,
+        fetched,
+        inserted: stored.inserted,
+        updated: stored.updated,
+        not_modified,
+        refresh_frequency,
+        moved_to,
+        duration_ms: now_timestamp_ms() - time_before,
+    })
 }
@@ -1,30 +1,40 @@
 use std::collections::BTreeMap;
 
+use crate::services::scheduler::FetchScheduler;
+
 pub async fn request_rss(
+  scheduler: &FetchScheduler,
   urls: &Vec<String>,
   _global_item_per_feed: i64,
   _item_per_feed: &Option<BTreeMap<String, i32>>,
 ) {
   let futures: Vec<_> = urls
     .into_iter()
-    .map(|url| {
-      reqwest::get(url.to_string())
+    .map(|url| async move {
+      // the permit is kept until the body is read
+      let _permit = scheduler.acquire(url).await;
+      let response = reqwest::get(url.to_string()).await?;
+      let url = response.url().to_string();
+      response.bytes().await?;
+      Ok::<String, reqwest::Error>(url)
     })
     .collect();
 
   let results = futures::future::join_all(futures).await;
   for result in results {
-    println!("Result: {}", result.unwrap().url());
+    println!("Result: {}", result.unwrap());
   }
 }
 
 #[cfg(test)]
 mod tests {
     use super::request_rss;
+    use crate::services::scheduler::FetchScheduler;
 
   #[tokio::test]
   async fn test_i_can_get_rss() {
     request_rss(
+      &FetchScheduler::new(32, 2, 500),
       &(&["https://4thehoard.com/panya?url=https://www3.nhk.or.jp/news/easy/&limit=10", "https://www.lemonde.fr/economie/rss_full.xml", "https://techcrunch.com/feed/"])
         .iter().map(|s| s.to_string()).collect(), 
         2, 
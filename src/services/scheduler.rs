@@ -0,0 +1,146 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use rocket::tokio::{
+    sync::{OwnedSemaphorePermit, Semaphore},
+    time::{sleep_until, Instant},
+};
+use url::Url;
+
+use crate::config::Settings;
+
+// hosts kept track of before the idle ones are forgotten
+const MAX_IDLE_HOSTS: usize = 1024;
+
+struct HostSlot {
+    permits: Arc<Semaphore>,
+    // no request to the host starts before then
+    next_start: Instant,
+}
+
+/// FetchScheduler bounds the outgoing requests: how many run at once overall,
+/// how many run at once to a single host, and how close to each other
+/// two requests to the same host start. A single one is managed by Rocket,
+/// every outgoing request going through it.
+pub struct FetchScheduler {
+    permits: Arc<Semaphore>,
+    max_per_host: usize,
+    host_delay: Duration,
+    hosts: Mutex<HashMap<String, HostSlot>>,
+}
+
+/// FetchPermit lets a request run. It should be kept until the response's body is read.
+pub struct FetchPermit {
+    _host: OwnedSemaphorePermit,
+    _global: OwnedSemaphorePermit,
+}
+
+impl FetchScheduler {
+    pub fn new(max_concurrency: usize, max_per_host: usize, host_delay: u64) -> Self {
+        FetchScheduler {
+            permits: Arc::new(Semaphore::new(max_concurrency.max(1))),
+            max_per_host: max_per_host.max(1),
+            host_delay: Duration::from_millis(host_delay),
+            hosts: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// from_settings sets the limits of a scheduler up from the settings
+    pub fn from_settings(settings: &Settings) -> Self {
+        FetchScheduler::new(
+            settings.fetch_max_concurrency,
+            settings.fetch_max_per_host,
+            settings.fetch_host_delay,
+        )
+    }
+
+    /// host_slot runs `f` over the slot of `host`, forgetting the idle hosts
+    /// once too many are kept track of.
+    fn host_slot<T>(&self, host: &str, f: impl FnOnce(&mut HostSlot, Instant) -> T) -> T {
+        let now = Instant::now();
+        let mut hosts = self.hosts.lock().unwrap_or_else(|err| err.into_inner());
+        if hosts.len() > MAX_IDLE_HOSTS {
+            hosts.retain(|_, slot| {
+                slot.next_start > now || slot.permits.available_permits() < self.max_per_host
+            });
+        }
+        let slot = hosts.entry(host.to_string()).or_insert_with(|| HostSlot {
+            permits: Arc::new(Semaphore::new(self.max_per_host)),
+            next_start: now,
+        });
+        f(slot, now)
+    }
+
+    /// acquire waits until a request to `url` may run
+    pub async fn acquire(&self, url: &str) -> FetchPermit {
+        let host = Url::parse(url)
+            .ok()
+            .and_then(|url| url.host_str().map(str::to_string))
+            .unwrap_or_else(|| url.to_string());
+        let host_permits = self.host_slot(&host, |slot, _| Arc::clone(&slot.permits));
+        // the semaphores are never closed
+        let host_permit = host_permits
+            .acquire_owned()
+            .await
+            .expect("fetch scheduler host semaphore closed");
+        // the start is reserved once a host permit is held, so that the delay
+        // separates requests that actually run, not the ones waiting for a permit
+        let start = self.host_slot(&host, |slot, now| {
+            let start = slot.next_start.max(now);
+            slot.next_start = start + self.host_delay;
+            start
+        });
+        sleep_until(start).await;
+        let global_permit = Arc::clone(&self.permits)
+            .acquire_owned()
+            .await
+            .expect("fetch scheduler semaphore closed");
+        FetchPermit {
+            _host: host_permit,
+            _global: global_permit,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn test_i_can_schedule_fetches() {
+        let scheduler = FetchScheduler::new(3, 2, 1000);
+        let start = Instant::now();
+
+        let first = scheduler.acquire("https://a.com/feed").await;
+        let second = scheduler.acquire("https://a.com/other").await;
+        // the same host is delayed
+        assert_eq!(Instant::now() - start, Duration::from_millis(1000));
+        // another host is not
+        let third = scheduler.acquire("https://b.com/feed").await;
+        assert_eq!(Instant::now() - start, Duration::from_millis(1000));
+
+        // the global cap is reached
+        assert!(rocket::tokio::time::timeout(
+            Duration::from_secs(10),
+            scheduler.acquire("https://c.com/feed")
+        )
+        .await
+        .is_err());
+        drop(third);
+        let _fourth = scheduler.acquire("https://c.com/feed").await;
+
+        // the host cap is reached
+        assert!(rocket::tokio::time::timeout(
+            Duration::from_secs(10),
+            scheduler.acquire("https://a.com/feed")
+        )
+        .await
+        .is_err());
+        drop(first);
+        drop(second);
+        let _fifth = scheduler.acquire("https://a.com/feed").await;
+    }
+}
@@ -0,0 +1,357 @@
+use std::sync::OnceLock;
+
+use crate::{
+    config::Settings,
+    converters::{
+        atom_feed::atom_to_articles, json_api::json_api_to_articles,
+        json_feed::json_feed_to_articles, rss_feed::rss_to_articles, scraper::scraper_to_articles,
+        string::to_articles,
+    },
+    entities::{
+        channel::{Channel, SourceType},
+        potential_articles::PotentialArticle,
+    },
+    error::Error,
+    services::{
+        bakery::request_bakery,
+        fetch::{conditional_get, FetchError, FetchOutcome, FetchResponse},
+        scheduler::FetchScheduler,
+    },
+};
+
+static ATOM_CONTENT_TYPES: [&str; 1] = ["application/atom+xml"];
+static JSON_FEED_CONTENT_TYPES: [&str; 1] = ["application/feed+json"];
+static RSS_CONTENT_TYPES: [&str; 2] = ["application/xml", "application/rss"];
+
+static REGISTRY: OnceLock<SourceRegistry> = OnceLock::new();
+
+fn has_content_type(content_type: &str, content_types: &[&str]) -> bool {
+    content_types.iter().any(|ct| content_type.contains(ct))
+}
+
+/// SourceFetcher knows how to recognize, fetch and parse one type of source
+#[rocket::async_trait]
+pub trait SourceFetcher: Send + Sync {
+    fn source_type(&self) -> SourceType;
+
+    /// detect tells if a response, given its content type and body, is a source of this type
+    fn detect(&self, content_type: &str, body: &str) -> bool;
+
+    /// fetch downloads the channel's source, through `scheduler`
+    async fn fetch(
+        &self,
+        scheduler: &FetchScheduler,
+        settings: &Settings,
+        channel: &Channel,
+    ) -> Result<FetchResponse, FetchError>;
+
+    /// parse turns a fetched source into articles belonging to `channel`
+    fn parse(&self, channel: &Channel, body: &[u8]) -> Result<Vec<PotentialArticle>, Error>;
+
+    /// is_polled tells if the refresh worker refreshes the channels of this type.
+    /// The others are refreshed on demand.
+    fn is_polled(&self) -> bool {
+        true
+    }
+}
+
+/// FeedFetcher fetches RSS, Atom and JSON feeds, with conditional requests
+pub struct FeedFetcher(pub SourceType);
+
+#[rocket::async_trait]
+impl SourceFetcher for FeedFetcher {
+    fn source_type(&self) -> SourceType {
+        self.0
+    }
+
+    fn detect(&self, content_type: &str, body: &str) -> bool {
+        let is_xml = has_content_type(content_type, &RSS_CONTENT_TYPES);
+        match self.0 {
+            SourceType::JsonFeed => {
+                has_content_type(content_type, &JSON_FEED_CONTENT_TYPES)
+                    || (content_type.contains("application/json")
+                        && body.contains("jsonfeed.org/version"))
+            }
+            SourceType::AtomFeed => {
+                has_content_type(content_type, &ATOM_CONTENT_TYPES)
+                    || (is_xml && body.contains("<feed") && !body.contains("<rss"))
+            }
+            SourceType::RSSFeed => is_xml,
+            _ => false,
+        }
+    }
+
+    async fn fetch(
+        &self,
+        scheduler: &FetchScheduler,
+        _: &Settings,
+        channel: &Channel,
+    ) -> Result<FetchResponse, FetchError> {
+        conditional_get(scheduler, channel).await
+    }
+
+    fn parse(&self, channel: &Channel, body: &[u8]) -> Result<Vec<PotentialArticle>, Error> {
+        match self.0 {
+            SourceType::RSSFeed => rss_to_articles(body, channel),
+            SourceType::AtomFeed => atom_to_articles(body, channel),
+            SourceType::JsonFeed => json_feed_to_articles(body, channel),
+            source_type => Err(Error(format!(
+                "{} is not a feed source type",
+                source_type.as_str()
+            ))),
+        }
+    }
+}
+
+/// BakeryFetcher scrapes web pages through bakery. It recognizes any page,
+/// hence comes last in the registry. Bakery channels are refreshed on demand, by get_url.
+pub struct BakeryFetcher;
+
+#[rocket::async_trait]
+impl SourceFetcher for BakeryFetcher {
+    fn source_type(&self) -> SourceType {
+        SourceType::Bakery
+    }
+
+    fn detect(&self, _: &str, _: &str) -> bool {
+        true
+    }
+
+    async fn fetch(
+        &self,
+        scheduler: &FetchScheduler,
+        settings: &Settings,
+        channel: &Channel,
+    ) -> Result<FetchResponse, FetchError> {
+        Ok(FetchResponse {
+            outcome: FetchOutcome::Fetched {
+                body: request_bakery(scheduler, &settings.api_path, &channel.url).await?,
+                etag: None,
+                last_modified: None,
+                link: None,
+            },
+            moved_to: None,
+        })
+    }
+
+    fn parse(&self, channel: &Channel, body: &[u8]) -> Result<Vec<PotentialArticle>, Error> {
+        Ok(to_articles(&String::from_utf8_lossy(body))
+            .into_iter()
+            .map(|article| PotentialArticle {
+                channel_name: Some(channel.name.clone()),
+                channel_id: Some(channel.id),
+                ..article
+            })
+            .collect())
+    }
+
+    fn is_polled(&self) -> bool {
+        false
+    }
+}
+
+/// ScraperFetcher reads web pages in-process, with the channel's scraper rules.
+/// Pages are never detected as such, the rules being needed to read them.
+pub struct ScraperFetcher;
+
+#[rocket::async_trait]
+impl SourceFetcher for ScraperFetcher {
+    fn source_type(&self) -> SourceType {
+        SourceType::SCRAPER
+    }
+
+    fn detect(&self, _: &str, _: &str) -> bool {
+        false
+    }
+
+    async fn fetch(
+        &self,
+        scheduler: &FetchScheduler,
+        _: &Settings,
+        channel: &Channel,
+    ) -> Result<FetchResponse, FetchError> {
+        conditional_get(scheduler, channel).await
+    }
+
+    fn parse(&self, channel: &Channel, body: &[u8]) -> Result<Vec<PotentialArticle>, Error> {
+        scraper_to_articles(body, channel)
+    }
+}
+
+/// JsonApiFetcher reads JSON APIs, with the channel's JSON API mapping.
+/// Like scraped pages, they are never detected as such.
+pub struct JsonApiFetcher;
+
+#[rocket::async_trait]
+impl SourceFetcher for JsonApiFetcher {
+    fn source_type(&self) -> SourceType {
+        SourceType::JSON_API
+    }
+
+    fn detect(&self, _: &str, _: &str) -> bool {
+        false
+    }
+
+    async fn fetch(
+        &self,
+        scheduler: &FetchScheduler,
+        _: &Settings,
+        channel: &Channel,
+    ) -> Result<FetchResponse, FetchError> {
+        conditional_get(scheduler, channel).await
+    }
+
+    fn parse(&self, channel: &Channel, body: &[u8]) -> Result<Vec<PotentialArticle>, Error> {
+        json_api_to_articles(body, channel)
+    }
+}
+
+/// SourceRegistry holds a fetcher per source type. Detection tries them
+/// in the order they were registered, the first recognizing a source wins.
+pub struct SourceRegistry {
+    fetchers: Vec<Box<dyn SourceFetcher>>,
+}
+
+impl Default for SourceRegistry {
+    fn default() -> Self {
+        SourceRegistry::new()
+            .register(FeedFetcher(SourceType::JsonFeed))
+            .register(FeedFetcher(SourceType::AtomFeed))
+            .register(FeedFetcher(SourceType::RSSFeed))
+            .register(ScraperFetcher)
+            .register(JsonApiFetcher)
+            .register(BakeryFetcher)
+    }
+}
+
+impl SourceRegistry {
+    pub fn new() -> Self {
+        SourceRegistry { fetchers: vec![] }
+    }
+
+    /// register adds a fetcher, replacing the one of the same source type, if any
+    pub fn register(mut self, fetcher: impl SourceFetcher + 'static) -> Self {
+        self.fetchers
+            .retain(|registered| registered.source_type() != fetcher.source_type());
+        self.fetchers.push(Box::new(fetcher));
+        self
+    }
+
+    pub fn get(&self, source_type: SourceType) -> Result<&dyn SourceFetcher, Error> {
+        self.fetchers
+            .iter()
+            .find(|fetcher| fetcher.source_type() == source_type)
+            .map(|fetcher| fetcher.as_ref())
+            .ok_or_else(|| Error(format!("no fetcher for {} sources", source_type.as_str())))
+    }
+
+    /// find returns the registered source type named `name`
+    pub fn find(&self, name: &str) -> Option<SourceType> {
+        self.fetchers
+            .iter()
+            .map(|fetcher| fetcher.source_type())
+            .find(|source_type| source_type.as_str() == name)
+    }
+
+    /// detect returns the source type of a response, given its content type and body
+    pub fn detect(&self, content_type: &str, body: &str) -> Option<SourceType> {
+        self.fetchers
+            .iter()
+            .find(|fetcher| fetcher.detect(content_type, body))
+            .map(|fetcher| fetcher.source_type())
+    }
+
+    /// polled_types lists the source types the refresh worker refreshes
+    pub fn polled_types(&self) -> Vec<SourceType> {
+        self.fetchers
+            .iter()
+            .filter(|fetcher| fetcher.is_polled())
+            .map(|fetcher| fetcher.source_type())
+            .collect()
+    }
+
+    pub fn is_polled(&self, source_type: SourceType) -> bool {
+        self.get(source_type)
+            .is_ok_and(|fetcher| fetcher.is_polled())
+    }
+}
+
+/// registry returns the registry every source is dispatched through
+pub fn registry() -> &'static SourceRegistry {
+    REGISTRY.get_or_init(SourceRegistry::default)
+}
+
+/// preview_source fetches and parses a channel's source without storing anything,
+/// so that its settings can be tried out before they are saved.
+pub async fn preview_source(
+    scheduler: &FetchScheduler,
+    settings: &Settings,
+    channel: &Channel,
+) -> Result<Vec<PotentialArticle>, FetchError> {
+    let fetcher = registry().get(channel.source_type)?;
+    let preview = Channel {
+        etag: None,
+        last_modified: None,
+        ..channel.clone()
+    };
+    match fetcher.fetch(scheduler, settings, &preview).await?.outcome {
+        FetchOutcome::Fetched { body, .. } => Ok(fetcher.parse(&preview, &body)?),
+        FetchOutcome::NotModified => Ok(vec![]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct TestFetcher;
+
+    #[rocket::async_trait]
+    impl SourceFetcher for TestFetcher {
+        fn source_type(&self) -> SourceType {
+            SourceType::Other("test")
+        }
+
+        fn detect(&self, content_type: &str, _: &str) -> bool {
+            content_type == "text/test"
+        }
+
+        async fn fetch(
+            &self,
+            _: &FetchScheduler,
+            _: &Settings,
+            _: &Channel,
+        ) -> Result<FetchResponse, FetchError> {
+            Err(Error::str("not fetched").into())
+        }
+
+        fn parse(&self, _: &Channel, _: &[u8]) -> Result<Vec<PotentialArticle>, Error> {
+            Ok(vec![])
+        }
+    }
+
+    #[test]
+    fn test_i_can_register_a_source() {
+        let registry = SourceRegistry::new()
+            .register(FeedFetcher(SourceType::RSSFeed))
+            .register(TestFetcher)
+            .register(BakeryFetcher);
+
+        assert_eq!(
+            registry.detect("text/test", ""),
+            Some(SourceType::Other("test"))
+        );
+        assert_eq!(
+            registry.detect("application/rss+xml", "<rss></rss>"),
+            Some(SourceType::RSSFeed)
+        );
+        assert_eq!(registry.detect("text/html", ""), Some(SourceType::Bakery));
+        assert_eq!(registry.find("test"), Some(SourceType::Other("test")));
+        assert_eq!(registry.find("atom_feed"), None);
+        assert!(registry.get(SourceType::AtomFeed).is_err());
+        assert_eq!(
+            registry.polled_types(),
+            vec![SourceType::RSSFeed, SourceType::Other("test")]
+        );
+    }
+}
@@ -0,0 +1,431 @@
+use atom_syndication::Feed;
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use serde::Deserialize;
+use sha1::Sha1;
+use sha2::{Sha256, Sha384, Sha512};
+use url::Url;
+
+use crate::{
+    config::Settings,
+    db::{channel::Channels, mongo::Handle},
+    entities::channel::{Channel, SourceType, WebSubSubscription},
+    error::Error,
+    services::scheduler::FetchScheduler,
+};
+
+const ATOM_NAMESPACE: &str = "http://www.w3.org/2005/Atom";
+
+/// WebSubLinks are the hub a feed advertises, and the topic to subscribe to on it
+#[derive(Clone, Debug, PartialEq)]
+pub struct WebSubLinks {
+    pub hub: String,
+    pub topic: String,
+}
+
+#[derive(Deserialize)]
+struct JsonFeedHub {
+    #[serde(rename = "type")]
+    hub_type: String,
+    url: String,
+}
+
+#[derive(Deserialize)]
+struct JsonFeedHubs {
+    feed_url: Option<String>,
+    #[serde(default)]
+    hubs: Vec<JsonFeedHub>,
+}
+
+/// links_from_header reads the hub and self links of a `Link` header,
+/// such as `<https://hub.com/>; rel="hub", <https://a.com/feed>; rel="self"`.
+fn links_from_header(link_header: &str) -> (Option<String>, Option<String>) {
+    let (mut hub, mut topic) = (None, None);
+    for link in link_header.split(',') {
+        let mut parts = link.split(';');
+        let url = match parts
+            .next()
+            .map(str::trim)
+            .and_then(|url| url.strip_prefix('<').and_then(|url| url.strip_suffix('>')))
+        {
+            Some(res) => res.to_string(),
+            None => continue,
+        };
+        let rels = parts
+            .filter_map(|param| param.trim().strip_prefix("rel="))
+            .flat_map(|rel| rel.trim_matches('"').split_whitespace())
+            .map(str::to_lowercase)
+            .collect::<Vec<String>>();
+        if rels.iter().any(|rel| rel == "hub") && hub.is_none() {
+            hub = Some(url.clone());
+        }
+        if rels.iter().any(|rel| rel == "self") && topic.is_none() {
+            topic = Some(url);
+        }
+    }
+    (hub, topic)
+}
+
+/// links_from_document reads the hub and self links of a feed: atom:link elements
+/// of RSS and Atom feeds, `hubs` and `feed_url` of JSON feeds.
+fn links_from_document(source_type: SourceType, body: &[u8]) -> (Option<String>, Option<String>) {
+    let links: Vec<(String, String)> = match source_type {
+        SourceType::RSSFeed => match rss::Channel::read_from(body) {
+            Ok(channel) => {
+                let prefix = channel
+                    .namespaces()
+                    .iter()
+                    .find(|(_, url)| url.as_str() == ATOM_NAMESPACE)
+                    .map(|(prefix, _)| prefix.as_str())
+                    .unwrap_or("atom");
+                channel
+                    .extensions()
+                    .get(prefix)
+                    .and_then(|atom| atom.get("link"))
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|link| {
+                        Some((
+                            link.attrs().get("rel")?.to_string(),
+                            link.attrs().get("href")?.to_string(),
+                        ))
+                    })
+                    .collect()
+            }
+            Err(_) => vec![],
+        },
+        SourceType::AtomFeed => match Feed::read_from(body) {
+            Ok(feed) => feed
+                .links()
+                .iter()
+                .map(|link| (link.rel().to_string(), link.href().to_string()))
+                .collect(),
+            Err(_) => vec![],
+        },
+        SourceType::JsonFeed => match serde_json::from_slice::<JsonFeedHubs>(body) {
+            Ok(feed) => feed
+                .hubs
+                .into_iter()
+                .filter(|hub| hub.hub_type.eq_ignore_ascii_case("websub"))
+                .map(|hub| ("hub".to_string(), hub.url))
+                .chain(feed.feed_url.map(|url| ("self".to_string(), url)))
+                .collect(),
+            Err(_) => vec![],
+        },
+        _ => vec![],
+    };
+    let find = |rel: &str| {
+        links
+            .iter()
+            .find(|(link_rel, _)| link_rel == rel)
+            .map(|(_, href)| href.clone())
+    };
+    (find("hub"), find("self"))
+}
+
+/// discover_hub finds the WebSub hub of a fetched feed, looking at the `Link` headers
+/// of the response first, then at the document. The topic is the feed's self link,
+/// falling back on the url it was fetched from.
+pub fn discover_hub(
+    source_type: SourceType,
+    body: &[u8],
+    link_header: Option<&str>,
+    url: &str,
+) -> Option<WebSubLinks> {
+    let (header_hub, header_topic) = link_header.map(links_from_header).unwrap_or_default();
+    let (hub, topic) = match header_hub {
+        Some(hub) => (hub, header_topic),
+        None => match links_from_document(source_type, body) {
+            (Some(hub), topic) => (hub, topic),
+            (None, _) => return None,
+        },
+    };
+    Some(WebSubLinks {
+        hub,
+        topic: topic.unwrap_or_else(|| url.to_string()),
+    })
+}
+
+/// subscription_secret derives the secret of a channel's subscription from `websub_secret`,
+/// so that it does not need to be stored, and stays the same across lease renewals.
+pub fn subscription_secret(settings: &Settings, channel_id: i32, hub: &str, topic: &str) -> String {
+    let mut mac = match Hmac::<Sha256>::new_from_slice(settings.websub_secret.as_bytes()) {
+        Ok(res) => res,
+        Err(_) => return String::new(),
+    };
+    mac.update(format!("{} {} {}", channel_id, hub, topic).as_bytes());
+    hex::encode(mac.finalize().into_bytes())
+}
+
+fn verify_hmac<M: Mac + hmac::digest::KeyInit>(
+    secret: &[u8],
+    body: &[u8],
+    signature: &[u8],
+) -> bool {
+    match <M as hmac::digest::KeyInit>::new_from_slice(secret) {
+        Ok(mut mac) => {
+            mac.update(body);
+            mac.verify_slice(signature).is_ok()
+        }
+        Err(_) => false,
+    }
+}
+
+/// verify_signature checks an `X-Hub-Signature` header, such as `sha256=<hex>`,
+/// against the body of a notification.
+pub fn verify_signature(secret: &str, signature_header: &str, body: &[u8]) -> bool {
+    let (method, signature) = match signature_header.trim().split_once('=') {
+        Some(res) => res,
+        None => return false,
+    };
+    let signature = match hex::decode(signature) {
+        Ok(res) => res,
+        Err(_) => return false,
+    };
+    let secret = secret.as_bytes();
+    match method.to_lowercase().as_str() {
+        "sha1" => verify_hmac::<Hmac<Sha1>>(secret, body, &signature),
+        "sha256" => verify_hmac::<Hmac<Sha256>>(secret, body, &signature),
+        "sha384" => verify_hmac::<Hmac<Sha384>>(secret, body, &signature),
+        "sha512" => verify_hmac::<Hmac<Sha512>>(secret, body, &signature),
+        _ => false,
+    }
+}
+
+/// callback_url returns the url hubs notify a channel's updates to
+pub fn callback_url(settings: &Settings, channel_id: i32) -> String {
+    format!(
+        "{}/panya/websub/{}",
+        settings.public_url.trim_end_matches('/'),
+        channel_id
+    )
+}
+
+/// verify_subscription returns the subscription as verified by the hub at `now` (s), for the
+/// lease it granted, no longer than `websub_lease_seconds`. Verifications of subscriptions
+/// panya is not waiting for are refused, as anyone can send them.
+pub fn verify_subscription(
+    settings: &Settings,
+    subscription: &WebSubSubscription,
+    lease_seconds: Option<i64>,
+    now: i64,
+) -> Option<WebSubSubscription> {
+    if !subscription.awaits_verification(now) {
+        return None;
+    }
+    let lease_seconds =
+        lease_seconds.map(|lease_seconds| lease_seconds.clamp(0, settings.websub_lease_seconds));
+    Some(WebSubSubscription {
+        verified: true,
+        pending: false,
+        lease_seconds,
+        lease_expires: lease_seconds.map(|lease_seconds| now + lease_seconds),
+        ..subscription.clone()
+    })
+}
+
+/// request_subscription asks a hub to push the updates of a channel's topic.
+/// The subscription is stored as pending until the hub verifies it.
+/// Renewing an active subscription keeps it active meanwhile.
+pub async fn request_subscription(
+    handle: &Handle,
+    scheduler: &FetchScheduler,
+    settings: &Settings,
+    channel: &Channel,
+    links: &WebSubLinks,
+) -> Result<(), Error> {
+    if let Some(reason) = settings.websub_disabled_reason() {
+        return Err(Error(format!("websub is disabled, {}", reason)));
+    }
+    // the secret would travel in clear to other hubs, and their notifications could not be trusted
+    if !Url::parse(&links.hub).is_ok_and(|hub| hub.scheme() == "https") {
+        return Err(Error(format!(
+            "hub {} is not https, its notifications could not be authenticated",
+            links.hub
+        )));
+    }
+    let now = Utc::now().timestamp();
+    let subscription = match &channel.websub {
+        Some(current) if current.hub == links.hub && current.topic == links.topic => {
+            WebSubSubscription {
+                requested_at: now,
+                pending: true,
+                ..current.clone()
+            }
+        }
+        _ => WebSubSubscription {
+            hub: links.hub.clone(),
+            topic: links.topic.clone(),
+            verified: false,
+            pending: true,
+            requested_at: now,
+            lease_seconds: None,
+            lease_expires: None,
+        },
+    };
+    Channels::new(handle, "panya")?
+        .update_websub(channel.id, Some(&subscription))
+        .await;
+
+    let _permit = scheduler.acquire(&links.hub).await;
+    reqwest::Client::new()
+        .post(&links.hub)
+        .form(&[
+            ("hub.mode", "subscribe"),
+            ("hub.topic", links.topic.as_str()),
+            ("hub.callback", callback_url(settings, channel.id).as_str()),
+            (
+                "hub.secret",
+                subscription_secret(settings, channel.id, &links.hub, &links.topic).as_str(),
+            ),
+            (
+                "hub.lease_seconds",
+                settings.websub_lease_seconds.to_string().as_str(),
+            ),
+        ])
+        .send()
+        .await?
+        .error_for_status()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_i_can_discover_hub() {
+        let rss = br#"<?xml version="1.0"?>
+<rss version="2.0" xmlns:atom="http://www.w3.org/2005/Atom">
+  <channel>
+    <title>Test</title>
+    <link>https://example.com</link>
+    <description>Test</description>
+    <atom:link rel="hub" href="https://pubsubhubbub.appspot.com/"/>
+    <atom:link rel="self" type="application/rss+xml" href="https://example.com/feed"/>
+  </channel>
+</rss>"#;
+        assert_eq!(
+            discover_hub(SourceType::RSSFeed, rss, None, "http://example.com/feed"),
+            Some(WebSubLinks {
+                hub: "https://pubsubhubbub.appspot.com/".to_string(),
+                topic: "https://example.com/feed".to_string(),
+            })
+        );
+
+        let atom = br#"<?xml version="1.0" encoding="utf-8"?>
+<feed xmlns="http://www.w3.org/2005/Atom">
+  <title>Test</title>
+  <id>urn:uuid:60a76c80-d399-11d9-b93C-0003939e0af6</id>
+  <updated>2003-12-13T18:30:02Z</updated>
+  <link rel="hub" href="https://hub.example.com/"/>
+</feed>"#;
+        assert_eq!(
+            discover_hub(SourceType::AtomFeed, atom, None, "https://example.com/atom"),
+            Some(WebSubLinks {
+                hub: "https://hub.example.com/".to_string(),
+                topic: "https://example.com/atom".to_string(),
+            })
+        );
+
+        let json = br#"{"version": "https://jsonfeed.org/version/1.1", "feed_url": "https://example.com/feed.json",
+            "hubs": [{"type": "rssCloud", "url": "https://cloud.com/"}, {"type": "WebSub", "url": "https://hub.com/"}], "items": []}"#;
+        assert_eq!(
+            discover_hub(SourceType::JsonFeed, json, None, "https://example.com/json"),
+            Some(WebSubLinks {
+                hub: "https://hub.com/".to_string(),
+                topic: "https://example.com/feed.json".to_string(),
+            })
+        );
+
+        let header =
+            r#"<https://example.com/topic>; rel="self", <https://header-hub.com/>; rel="hub""#;
+        assert_eq!(
+            discover_hub(
+                SourceType::AtomFeed,
+                atom,
+                Some(header),
+                "https://example.com/atom"
+            ),
+            Some(WebSubLinks {
+                hub: "https://header-hub.com/".to_string(),
+                topic: "https://example.com/topic".to_string(),
+            })
+        );
+        assert_eq!(
+            discover_hub(SourceType::RSSFeed, b"<rss></rss>", None, "https://a.com"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_i_can_verify_signature() {
+        let body = b"<feed></feed>";
+        let mut mac = Hmac::<Sha256>::new_from_slice(b"secret").unwrap();
+        mac.update(body);
+        let signature = hex::encode(mac.finalize().into_bytes());
+        let mut mac = Hmac::<Sha1>::new_from_slice(b"secret").unwrap();
+        mac.update(body);
+        let sha1_signature = hex::encode(mac.finalize().into_bytes());
+
+        assert!(verify_signature(
+            "secret",
+            &format!("sha256={}", signature),
+            body
+        ));
+        assert!(verify_signature(
+            "secret",
+            &format!("sha1={}", sha1_signature),
+            body
+        ));
+        assert!(!verify_signature(
+            "other",
+            &format!("sha256={}", signature),
+            body
+        ));
+        assert!(!verify_signature(
+            "secret",
+            &format!("sha256={}", signature),
+            b"<feed/>"
+        ));
+        assert!(!verify_signature(
+            "secret",
+            &format!("md5={}", signature),
+            body
+        ));
+        assert!(!verify_signature("secret", "sha256=nothex", body));
+    }
+
+    #[test]
+    fn test_i_only_verify_pending_subscriptions() {
+        let settings = Settings::defaults();
+        let subscription = WebSubSubscription {
+            hub: "https://hub.com".to_string(),
+            topic: "https://a.com/feed".to_string(),
+            verified: false,
+            pending: true,
+            requested_at: 1000,
+            lease_seconds: None,
+            lease_expires: None,
+        };
+
+        let verified = verify_subscription(&settings, &subscription, Some(i64::MAX), 2000).unwrap();
+        assert!(verified.verified);
+        assert!(!verified.pending);
+        assert_eq!(verified.lease_seconds, Some(settings.websub_lease_seconds));
+        assert_eq!(
+            verified.lease_expires,
+            Some(2000 + settings.websub_lease_seconds)
+        );
+        // a verification was already received
+        assert_eq!(
+            verify_subscription(&settings, &verified, Some(10), 2000),
+            None
+        );
+        // the request is too old
+        assert_eq!(
+            verify_subscription(&settings, &subscription, Some(10), 1000 + 86401),
+            None
+        );
+    }
+}
@@ -1,4 +1,4 @@
-use chrono::{DateTime, Duration, Utc};
+use chrono::{DateTime, Duration, NaiveDate, NaiveDateTime, Utc};
 use serde::Deserialize;
 use std::time::{SystemTime, UNIX_EPOCH};
 use url::{Position, Url};
@@ -23,8 +23,30 @@ pub fn datetime_minus_minutes(minus_minutes: i64, dt: DateTime<Utc>) -> i64 {
     (dt - Duration::minutes(minus_minutes)).timestamp()
 }
 
-pub fn now_minus_minutes(minus_minutes: i64) -> i64 {
-    datetime_minus_minutes(minus_minutes, Utc::now())
+/// parse_feed_date turns a feed date (RFC 2822 or RFC 3339) into a timestamp in ms
+pub fn parse_feed_date(date: &str) -> Option<i64> {
+    DateTime::parse_from_rfc2822(date.trim())
+        .or_else(|_| DateTime::parse_from_rfc3339(date.trim()))
+        .map(|dt| dt.timestamp_millis())
+        .ok()
+}
+
+/// parse_date_with_format turns a date into a timestamp in ms, reading it with `format`,
+/// a chrono format, and falling back on RFC 2822 and RFC 3339.
+/// Dates read with `format` are taken as UTC.
+pub fn parse_date_with_format(date: &str, format: Option<&str>) -> Option<i64> {
+    let date = date.trim();
+    format
+        .and_then(|format| {
+            NaiveDateTime::parse_from_str(date, format)
+                .or_else(|_| {
+                    NaiveDate::parse_from_str(date, format)
+                        .map(|date| date.and_hms_opt(0, 0, 0).unwrap_or_default())
+                })
+                .ok()
+        })
+        .map(|date| date.and_utc().timestamp_millis())
+        .or_else(|| parse_feed_date(date))
 }
 
 pub fn clean_url(input_url: &str) -> Result<String, url::ParseError> {
@@ -98,6 +120,16 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_i_can_parse_feed_date() {
+        assert_eq!(
+            parse_feed_date("Tue, 10 Jun 2003 04:00:00 GMT"),
+            Some(1055217600000)
+        );
+        assert_eq!(parse_feed_date("2003-06-10T04:00:00Z"), Some(1055217600000));
+        assert_eq!(parse_feed_date("yesterday"), None);
+    }
+
     #[test]
     fn test_i_can_clean_url() {
         let trial = "https://www3.nhk.or.jp/news/easy/?limit=5";
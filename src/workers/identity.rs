@@ -27,6 +27,8 @@ impl Into<User> for IdentityUser {
             id: self.id,
             username: self.login,
             channel_ids: self.channel_ids,
+            folders: vec![],
+            feed_token_hash: None,
         }
     }
 }
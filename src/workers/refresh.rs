@@ -0,0 +1,162 @@
+use std::{sync::Arc, time::Duration};
+
+use chrono::Utc;
+use mongodb::bson::doc;
+use rocket::tokio::{spawn, time::sleep};
+
+use crate::{
+    config::Settings,
+    db::{
+        channel::Channels,
+        model::{CollectionModel, SortOrder},
+        mongo::Handle,
+    },
+    entities::channel::{Channel, SourceType, WEBSUB_PENDING_TIMEOUT},
+    error::Error,
+    services::{
+        refresh::refresh_channel,
+        scheduler::FetchScheduler,
+        sources::registry,
+        websub::{request_subscription, WebSubLinks},
+    },
+};
+
+/// is_due tells if the channel should be refreshed at `now` (ms). Channels pushed
+/// by a hub are only polled at the websub refresh frequency, as a safety net.
+fn is_due(channel: &Channel, settings: &Settings, now: i64) -> bool {
+    let is_pushed = channel
+        .websub
+        .as_ref()
+        .is_some_and(|websub| websub.is_active(now / 1000));
+    let is_due = match is_pushed {
+        true => channel.is_due_every(
+            channel
+                .refresh_frequency
+                .max(settings.websub_refresh_frequency()),
+            now,
+        ),
+        false => channel.is_due(now),
+    };
+    is_due && channel.health.is_ready(now / 1000)
+}
+
+/// renew_subscriptions requests again the subscriptions whose lease is about to expire,
+/// and the ones the hub never verified.
+async fn renew_subscriptions(
+    db_handle: &Handle,
+    scheduler: &FetchScheduler,
+    settings: &Settings,
+    channels: &[Channel],
+) {
+    if !settings.websub_enabled() {
+        return;
+    }
+    let now = Utc::now().timestamp();
+    for channel in channels {
+        let websub = match &channel.websub {
+            Some(res) => res,
+            None => continue,
+        };
+        let is_pending = !websub.verified && websub.requested_at + WEBSUB_PENDING_TIMEOUT <= now;
+        if !is_pending && !websub.needs_renewal(now) {
+            continue;
+        }
+        let links = WebSubLinks {
+            hub: websub.hub.clone(),
+            topic: websub.topic.clone(),
+        };
+        match request_subscription(db_handle, scheduler, settings, channel, &links).await {
+            Ok(()) => println!(
+                "[INFO] CHANNEL {} ({}) renewed its subscription to hub {}",
+                channel.id, channel.url, websub.hub
+            ),
+            Err(err) => eprintln!(
+                "[ERR ] CHANNEL {} ({}) could not renew its subscription to hub {}: {}",
+                channel.id, channel.url, websub.hub, err
+            ),
+        }
+    }
+}
+
+/// run_refresh refreshes every feed channel whose refresh frequency has elapsed,
+/// skipping the suspended channels and the ones backing off after a failure.
+/// WebSub subscriptions are renewed along the way.
+async fn run_refresh(
+    db_handle: &Handle,
+    scheduler: &FetchScheduler,
+    settings: &Settings,
+) -> Result<(), Error> {
+    let channels_coll = Channels::new(db_handle, "panya")?;
+    let now = Utc::now().timestamp_millis();
+    let channels = channels_coll
+        .find(
+            doc! {"source_type": {"$in": registry()
+            .polled_types()
+            .iter()
+            .map(SourceType::as_str)
+            .collect::<Vec<&str>>()}},
+            Some("id"),
+            SortOrder::ASC,
+            None,
+        )
+        .await
+        .unwrap_or_default();
+    renew_subscriptions(db_handle, scheduler, settings, &channels).await;
+    let channels = channels
+        .into_iter()
+        .filter(|channel| is_due(channel, settings, now))
+        .collect::<Vec<_>>();
+    if channels.is_empty() {
+        return Ok(());
+    }
+
+    let futures = channels
+        .iter()
+        .map(|channel| refresh_channel(db_handle, scheduler, settings, channel));
+    for (channel, res) in channels
+        .iter()
+        .zip(futures::future::join_all(futures).await)
+    {
+        match res {
+            Ok(report) if report.not_modified => println!(
+                "[INFO] CHANNEL {} ({}) not modified in {}ms",
+                channel.id, channel.url, report.duration_ms
+            ),
+            Ok(report) => println!(
+                "[INFO] Refreshed CHANNEL {} ({}): {} fetched, {} new, {} updated in {}ms, next in {}ms",
+                channel.id,
+                report.moved_to.as_ref().unwrap_or(&channel.url),
+                report.fetched,
+                report.inserted,
+                report.updated,
+                report.duration_ms,
+                report.refresh_frequency
+            ),
+            Err(err) => eprintln!(
+                "[ERR ] Could not refresh CHANNEL {} ({}): {}",
+                channel.id, channel.url, err
+            ),
+        }
+    }
+    Ok(())
+}
+
+pub async fn refresh_worker(
+    db_handle: Arc<Handle>,
+    scheduler: Arc<FetchScheduler>,
+    settings: &Settings,
+) -> Result<(), Error> {
+    println!("[INFO] Starting Refresh WORKER setup");
+
+    let settings = settings.clone();
+    let interval = Duration::from_secs(settings.refresh_worker_interval);
+    spawn(async move {
+        loop {
+            if let Err(err) = run_refresh(&db_handle, &scheduler, &settings).await {
+                eprintln!("[ERR ] Refresh WORKER run failed: {}", err);
+            }
+            sleep(interval).await;
+        }
+    });
+    Ok(())
+}